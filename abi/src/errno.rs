@@ -0,0 +1,62 @@
+use core::fmt;
+
+// Failing syscalls return the negated error code in x0
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Errno(pub u64);
+
+impl Errno {
+    pub const EPERM: Errno = Errno(1);
+    pub const ENOENT: Errno = Errno(2);
+    pub const ESRCH: Errno = Errno(3);
+    pub const EINTR: Errno = Errno(4);
+    pub const E2BIG: Errno = Errno(7);
+    pub const EBADF: Errno = Errno(9);
+    pub const ECHILD: Errno = Errno(10);
+    pub const ENOMEM: Errno = Errno(12);
+    pub const EFAULT: Errno = Errno(14);
+    pub const ENODEV: Errno = Errno(19);
+    pub const EINVAL: Errno = Errno(22);
+    pub const EMFILE: Errno = Errno(24);
+    pub const EPIPE: Errno = Errno(32);
+    pub const ENOSYS: Errno = Errno(38);
+
+    // The value the kernel leaves in x0 to report this error
+    pub fn to_ret(self) -> u64 {
+        self.0.wrapping_neg()
+    }
+
+    // Return values in [-4095, -1] are errors, anything else is a result
+    pub fn check(ret: u64) -> Result<u64, Errno> {
+        if (ret as i64) < 0 && (ret as i64) >= -4095 {
+            Err(Errno(ret.wrapping_neg()))
+        } else {
+            Ok(ret)
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Errno::EPERM => "EPERM",
+            Errno::ENOENT => "ENOENT",
+            Errno::ESRCH => "ESRCH",
+            Errno::EINTR => "EINTR",
+            Errno::E2BIG => "E2BIG",
+            Errno::EBADF => "EBADF",
+            Errno::ECHILD => "ECHILD",
+            Errno::ENOMEM => "ENOMEM",
+            Errno::EFAULT => "EFAULT",
+            Errno::ENODEV => "ENODEV",
+            Errno::EINVAL => "EINVAL",
+            Errno::EMFILE => "EMFILE",
+            Errno::EPIPE => "EPIPE",
+            Errno::ENOSYS => "ENOSYS",
+            _ => "EUNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.name(), self.0)
+    }
+}
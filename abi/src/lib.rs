@@ -0,0 +1,15 @@
+#![no_std]
+
+// Definitions shared by the kernel and user programs.
+// A syscall is issued with `svc 0`, the number in x8 and up to six
+// arguments in x0-x5. The result is returned in x0, failures as the
+// negated errno code in [-4095, -1].
+
+pub mod errno;
+pub mod mman;
+pub mod signal;
+pub mod sysno;
+pub mod types;
+
+pub use errno::Errno;
+pub use sysno::Sysno;
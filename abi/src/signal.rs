@@ -0,0 +1,53 @@
+pub const NSIG: usize = 32;
+
+pub const SIGINT: usize = 2;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGSEGV: usize = 11;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGALRM: usize = 14;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+
+// Signal names without the SIG prefix
+const NAMES: [(usize, &str); 14] = [
+    (SIGINT, "INT"),
+    (SIGKILL, "KILL"),
+    (SIGUSR1, "USR1"),
+    (SIGSEGV, "SEGV"),
+    (SIGUSR2, "USR2"),
+    (SIGPIPE, "PIPE"),
+    (SIGALRM, "ALRM"),
+    (SIGTERM, "TERM"),
+    (SIGCHLD, "CHLD"),
+    (SIGCONT, "CONT"),
+    (SIGSTOP, "STOP"),
+    (SIGTSTP, "TSTP"),
+    (SIGTTIN, "TTIN"),
+    (SIGTTOU, "TTOU"),
+];
+
+pub fn name(sig: usize) -> Option<&'static str> {
+    NAMES.iter().find(|(n, _)| *n == sig).map(|(_, name)| *name)
+}
+
+// Accepts both "TERM" and "SIGTERM"
+pub fn from_name(name: &str) -> Option<usize> {
+    let name = name.strip_prefix("SIG").unwrap_or(name);
+    NAMES.iter().find(|(_, n)| *n == name).map(|(sig, _)| *sig)
+}
+
+// Special handler values for sigaction
+pub const SIG_DFL: u64 = 0;
+pub const SIG_IGN: u64 = 1;
+
+// How argument of sigprocmask
+pub const SIG_BLOCK: u64 = 0;
+pub const SIG_UNBLOCK: u64 = 1;
+pub const SIG_SETMASK: u64 = 2;
@@ -0,0 +1,78 @@
+#[repr(u64)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sysno {
+    GetPid = 0,
+    UartRead = 1,
+    UartWrite = 2,
+    Exec = 3,
+    Fork = 4,
+    Exit = 5,
+    MboxCall = 6,
+    Kill = 7,
+    Sigaction = 8,
+    SignalKill = 9,
+    Sigprocmask = 10,
+    Alarm = 11,
+    Setitimer = 12,
+    Pipe = 13,
+    Read = 14,
+    Write = 15,
+    Close = 16,
+    Dup2 = 17,
+    Open = 18,
+    Mmap = 19,
+    Munmap = 20,
+    Mprotect = 21,
+    Brk = 22,
+    Sbrk = 23,
+    Wait = 24,
+    Procinfo = 25,
+    Nanosleep = 26,
+    ClockGettime = 27,
+    Syslog = 28,
+    Sigreturn = 139,
+}
+
+impl Sysno {
+    pub const ALL: [Sysno; 30] = [
+        Sysno::GetPid,
+        Sysno::UartRead,
+        Sysno::UartWrite,
+        Sysno::Exec,
+        Sysno::Fork,
+        Sysno::Exit,
+        Sysno::MboxCall,
+        Sysno::Kill,
+        Sysno::Sigaction,
+        Sysno::SignalKill,
+        Sysno::Sigprocmask,
+        Sysno::Alarm,
+        Sysno::Setitimer,
+        Sysno::Pipe,
+        Sysno::Read,
+        Sysno::Write,
+        Sysno::Close,
+        Sysno::Dup2,
+        Sysno::Open,
+        Sysno::Mmap,
+        Sysno::Munmap,
+        Sysno::Mprotect,
+        Sysno::Brk,
+        Sysno::Sbrk,
+        Sysno::Wait,
+        Sysno::Procinfo,
+        Sysno::Nanosleep,
+        Sysno::ClockGettime,
+        Sysno::Syslog,
+        Sysno::Sigreturn,
+    ];
+}
+
+// Options of wait
+pub const WNOHANG: u64 = 1;
+
+// Actions of syslog
+pub const SYSLOG_READ_ALL: u64 = 3;
+pub const SYSLOG_CLEAR: u64 = 5;
+pub const SYSLOG_CONSOLE_LEVEL: u64 = 8;
+pub const SYSLOG_SIZE_BUFFER: u64 = 10;
@@ -0,0 +1,81 @@
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timespec {
+    pub sec: i64,
+    pub nsec: i64,
+}
+
+// Clocks of clock_gettime
+pub const CLOCK_MONOTONIC: u64 = 1;
+
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFREG: u32 = 0o100000;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stat {
+    pub ino: u64,
+    pub mode: u32,
+    pub nlink: u32,
+    pub size: u64,
+    pub blksize: u64,
+    pub blocks: u64,
+    pub mtime: Timespec,
+}
+
+pub const NAME_MAX: usize = 255;
+
+pub const DT_UNKNOWN: u8 = 0;
+pub const DT_FIFO: u8 = 1;
+pub const DT_CHR: u8 = 2;
+pub const DT_DIR: u8 = 4;
+pub const DT_REG: u8 = 8;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Dirent {
+    pub ino: u64,
+    pub kind: u8,
+    pub name_len: u8,
+    // Null terminated
+    pub name: [u8; NAME_MAX + 1],
+}
+
+impl Dirent {
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("")
+    }
+}
+
+pub const COMM_LEN: usize = 23;
+
+// Values of ProcInfo::state
+pub const PROC_RUNNING: u8 = b'R';
+pub const PROC_SLEEPING: u8 = b'S';
+pub const PROC_STOPPED: u8 = b'T';
+pub const PROC_ZOMBIE: u8 = b'Z';
+
+// An entry filled in by procinfo, 64 bytes without padding
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ProcInfo {
+    pub pid: u64,
+    // 0 if the process has no parent
+    pub ppid: u64,
+    pub cpu_ns: u64,
+    pub switches: u64,
+    // Bytes of memory mapped by the process
+    pub mem: u64,
+    pub state: u8,
+    // Null terminated, truncated if longer
+    pub name: [u8; COMM_LEN],
+}
+
+impl ProcInfo {
+    pub fn name(&self) -> &str {
+        let len = self.name.iter().position(|&c| c == 0).unwrap_or(COMM_LEN);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("")
+    }
+}
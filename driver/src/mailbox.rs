@@ -23,6 +23,16 @@ fn mailbox_write(channel: u8, data: u32) {
     }
 }
 
+// The GPU accesses the buffer in memory, behind the data cache of the ARM
+fn clean_invalidate(buf: &MailBox) {
+    const LINE: usize = 64;
+    let start = buf as *const MailBox as usize;
+    for line in (start..start + core::mem::size_of::<MailBox>()).step_by(LINE) {
+        unsafe { core::arch::asm!("dc civac, {}", in(reg) line) }
+    }
+    unsafe { core::arch::asm!("dsb sy") }
+}
+
 const CHANNEL_GPU: u8 = 8;
 
 #[allow(dead_code)]
@@ -56,7 +66,8 @@ pub fn get_arm_memory() -> (u32, u32) {
     (mailbox.get(5), mailbox.get(6))
 }
 
-#[repr(C, align(16))]
+// Cache line aligned, so no other data shares the lines the GPU writes
+#[repr(C, align(64))]
 pub struct MailBox {
     buffer: [u32; 36],
     pub len: u32,
@@ -76,14 +87,18 @@ impl MailBox {
     }
 
     pub fn call(&mut self, channel: u8) -> bool {
+        // The GPU takes a bus address, the low 32 bits of the buffer also
+        // when it is reached through the kernel's linear map
         let mailbox_ptr = self as *mut MailBox as u32;
         if mailbox_ptr & 0xF != 0 {
             panic!("Mailbox call failed");
         }
+        clean_invalidate(self);
         mailbox_write(channel, mailbox_ptr);
         if mailbox_read(channel) != mailbox_ptr | channel as u32 {
             panic!("Mailbox call failed");
         }
+        clean_invalidate(self);
         if self.buffer[1] != 0x8000_0000 {
             panic!("Mailbox call failed");
         }
@@ -1,83 +1,126 @@
-pub const MMIO_BASE: u32 = 0x3F00_0000;
+use core::ptr::addr_of_mut;
+
+pub const MMIO_BASE: usize = 0x3F00_0000;
+
+// Register blocks, each enum below holds offsets into one of them
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Block {
+    Aux,
+    Gpio,
+    Pm,
+    Mailbox,
+    Irq,
+}
+
+// Where the blocks sit on the BCM2837 of the Raspberry Pi 3. The kernel
+// relocates them into its device mapping at boot, then moves them to the
+// addresses found in the device tree when probing.
+static mut BASES: [usize; 5] = [
+    MMIO_BASE + 0x0021_5000,
+    MMIO_BASE + 0x0020_0000,
+    MMIO_BASE + 0x0010_0000,
+    MMIO_BASE + 0x0000_B880,
+    MMIO_BASE + 0x0000_B200,
+];
+
+pub fn base(block: Block) -> usize {
+    unsafe { BASES[block as usize] }
+}
+
+pub fn set_base(block: Block, addr: usize) {
+    unsafe {
+        BASES[block as usize] = addr;
+    }
+}
+
+// Move every block by offset, for callers that reach the peripherals
+// through a virtual mapping rather than at their physical addresses
+pub fn relocate(offset: usize) {
+    unsafe {
+        for base in (*addr_of_mut!(BASES)).iter_mut() {
+            *base += offset;
+        }
+    }
+}
 
 #[repr(u32)]
 #[derive(Copy, Clone)]
 #[allow(dead_code)]
 pub enum AuxReg {
-    Irq = 0x0021_5000,
-    Enable = 0x0021_5004,
-    MuIo = 0x0021_5040,
-    MuIer = 0x0021_5044,
-    MuIir = 0x0021_5048,
-    MuLcr = 0x0021_504C,
-    MuMcr = 0x0021_5050,
-    MuLsr = 0x0021_5054,
-    MuMsr = 0x0021_5058,
-    MuScratch = 0x0021_505C,
-    MuCntl = 0x0021_5060,
-    MuStat = 0x0021_5064,
-    MuBaud = 0x0021_5068,
+    Irq = 0x00,
+    Enable = 0x04,
+    MuIo = 0x40,
+    MuIer = 0x44,
+    MuIir = 0x48,
+    MuLcr = 0x4C,
+    MuMcr = 0x50,
+    MuLsr = 0x54,
+    MuMsr = 0x58,
+    MuScratch = 0x5C,
+    MuCntl = 0x60,
+    MuStat = 0x64,
+    MuBaud = 0x68,
 }
 
 #[repr(u32)]
 #[derive(Copy, Clone)]
 #[allow(dead_code)]
 pub enum GpioReg {
-    Gpfsel0 = 0x0020_0000,
-    Gpfsel1 = 0x0020_0004,
-    Gpfsel2 = 0x0020_0008,
-    Gpfsel3 = 0x0020_000C,
-    Gpfsel4 = 0x0020_0010,
-    Gpfsel5 = 0x0020_0014,
-    Gpset0 = 0x0020_001C,
-    Gpset1 = 0x0020_0020,
-    Gpclr0 = 0x0020_0028,
-    Gpclr1 = 0x0020_002C,
-    Gplev0 = 0x0020_0034,
-    Gplev1 = 0x0020_0038,
-    Gpeds0 = 0x0020_0040,
-    Gpeds1 = 0x0020_0044,
-    Gphen0 = 0x0020_004C,
-    Gphen1 = 0x0020_0050,
-    Gplen0 = 0x0020_0058,
-    Gplen1 = 0x0020_005C,
-    Gpren0 = 0x0020_0064,
-    Gpren1 = 0x0020_0068,
-    Gpfen0 = 0x0020_0070,
-    Gpfen1 = 0x0020_0074,
-    Gphs0 = 0x0020_007C,
-    Gphs1 = 0x0020_0080,
-    Gppud = 0x0020_0094,
-    GppudClk0 = 0x0020_0098,
-    GppudClk1 = 0x0020_009C,
+    Gpfsel0 = 0x00,
+    Gpfsel1 = 0x04,
+    Gpfsel2 = 0x08,
+    Gpfsel3 = 0x0C,
+    Gpfsel4 = 0x10,
+    Gpfsel5 = 0x14,
+    Gpset0 = 0x1C,
+    Gpset1 = 0x20,
+    Gpclr0 = 0x28,
+    Gpclr1 = 0x2C,
+    Gplev0 = 0x34,
+    Gplev1 = 0x38,
+    Gpeds0 = 0x40,
+    Gpeds1 = 0x44,
+    Gphen0 = 0x4C,
+    Gphen1 = 0x50,
+    Gplen0 = 0x58,
+    Gplen1 = 0x5C,
+    Gpren0 = 0x64,
+    Gpren1 = 0x68,
+    Gpfen0 = 0x70,
+    Gpfen1 = 0x74,
+    Gphs0 = 0x7C,
+    Gphs1 = 0x80,
+    Gppud = 0x94,
+    GppudClk0 = 0x98,
+    GppudClk1 = 0x9C,
 }
 
 #[repr(u32)]
 #[derive(Copy, Clone)]
 #[allow(dead_code)]
 pub enum PmReg {
-    Rstc = 0x0010_001C,
-    Rsts = 0x0010_0020,
-    Wdog = 0x0010_0024,
+    Rstc = 0x1C,
+    Rsts = 0x20,
+    Wdog = 0x24,
 }
 
 #[repr(u32)]
 #[derive(Copy, Clone)]
 #[allow(dead_code)]
 pub enum MailboxReg {
-    Read = 0x0000_B880,
-    Peek = 0x0000_B890,
-    Sender = 0x0000_B894,
-    Status = 0x0000_B898,
-    Config = 0x0000_B89C,
-    Write = 0x0000_B8A0,
+    Read = 0x00,
+    Peek = 0x10,
+    Sender = 0x14,
+    Status = 0x18,
+    Config = 0x1C,
+    Write = 0x20,
 }
 
 #[repr(u32)]
 #[derive(Copy, Clone)]
 #[allow(dead_code)]
 pub enum IrqReg {
-    S1 = 0x0000_B210,
+    S1 = 0x10,
 }
 
 #[repr(u32)]
@@ -92,13 +135,13 @@ pub enum MmioReg {
 }
 
 impl MmioReg {
-    pub fn addr(&self) -> u32 {
+    pub fn addr(&self) -> usize {
         match self {
-            MmioReg::Aux(reg) => MMIO_BASE + *reg as u32,
-            MmioReg::Gpio(reg) => MMIO_BASE + *reg as u32,
-            MmioReg::Pm(reg) => MMIO_BASE + *reg as u32,
-            MmioReg::MailboxReg(reg) => MMIO_BASE + *reg as u32,
-            MmioReg::Irq(reg) => MMIO_BASE + *reg as u32,
+            MmioReg::Aux(reg) => base(Block::Aux) + *reg as usize,
+            MmioReg::Gpio(reg) => base(Block::Gpio) + *reg as usize,
+            MmioReg::Pm(reg) => base(Block::Pm) + *reg as usize,
+            MmioReg::MailboxReg(reg) => base(Block::Mailbox) + *reg as usize,
+            MmioReg::Irq(reg) => base(Block::Irq) + *reg as usize,
         }
     }
 }
@@ -22,108 +22,95 @@ struct CpioHeader {
     check: [u8; 8],
 }
 
-impl CpioArchive {
-    pub fn load(data: *const u8) -> CpioArchive {
-        CpioArchive { data }
+// Iterator over the (name, content) pairs of an archive
+pub struct Files<'a> {
+    current: *const u8,
+    _archive: core::marker::PhantomData<&'a CpioArchive>,
+}
+
+const TRAILER: &str = "TRAILER!!!";
+
+fn align4(ptr: *const u8) -> *const u8 {
+    if ptr as usize % 4 != 0 {
+        unsafe { ptr.add(4 - (ptr as usize % 4)) }
+    } else {
+        ptr
     }
+}
 
-    pub fn print_file_list(&self) {
-        let mut current = self.data;
-        loop {
-            let header = unsafe { &*(current as *const CpioHeader) };
-            if header.magic != *b"070701" {
-                stdio::println!("Invalid CPIO magic");
-                break;
-            }
-            let namesize = u64::from_str_radix(
-                unsafe { core::str::from_utf8_unchecked(&header.namesize) },
-                16,
-            )
-            .unwrap();
-            let filesize = u64::from_str_radix(
-                unsafe { core::str::from_utf8_unchecked(&header.filesize) },
-                16,
-            )
-            .unwrap();
-            let name = unsafe {
-                core::str::from_utf8_unchecked(core::slice::from_raw_parts(
-                    current.add(110),
-                    namesize as usize,
-                ))
-            };
+// The name and content of the entry at ptr and the header following it,
+// None when there is no valid header at ptr
+unsafe fn entry<'a>(ptr: *const u8) -> Option<(&'a str, &'a [u8], *const u8)> {
+    let header = &*(ptr as *const CpioHeader);
+    if header.magic != *b"070701" {
+        return None;
+    }
+    let namesize =
+        u64::from_str_radix(core::str::from_utf8_unchecked(&header.namesize), 16).unwrap();
+    let filesize =
+        u64::from_str_radix(core::str::from_utf8_unchecked(&header.filesize), 16).unwrap();
+    let name = core::str::from_utf8_unchecked(core::slice::from_raw_parts(
+        ptr.add(110),
+        namesize as usize,
+    ));
+    let name = name.trim_end_matches('\0');
 
-            if name == "TRAILER!!!\0" {
-                break;
-            }
+    let data = align4(ptr.add(110 + namesize as usize));
+    let next = align4(data.add(filesize as usize));
+    Some((
+        name,
+        core::slice::from_raw_parts(data, filesize as usize),
+        next,
+    ))
+}
 
-            stdio::println!("{} ({} bytes)", name, filesize);
+impl<'a> Iterator for Files<'a> {
+    type Item = (&'a str, &'a [u8]);
 
-            current = unsafe { current.add(110 + namesize as usize) };
-            if current as usize % 4 != 0 {
-                current = unsafe { current.add(4 - (current as usize % 4)) };
-            }
+    fn next(&mut self) -> Option<Self::Item> {
+        let (name, data, next) = unsafe { entry(self.current)? };
+        if name == TRAILER {
+            return None;
+        }
+        self.current = next;
+        Some((name, data))
+    }
+}
 
-            current = unsafe { current.add(filesize as usize) };
+impl CpioArchive {
+    pub fn load(data: *const u8) -> CpioArchive {
+        CpioArchive { data }
+    }
 
-            if current as usize % 4 != 0 {
-                current = unsafe { current.add(4 - (current as usize % 4)) };
-            }
+    pub fn files(&self) -> Files<'_> {
+        Files {
+            current: self.data,
+            _archive: core::marker::PhantomData,
         }
     }
 
-    pub fn get_file(&self, filename: &str) -> Option<&[u8]> {
+    // Bytes up to the end of the TRAILER!!! entry, or of the last valid
+    // entry when the trailer is missing
+    pub fn size(&self) -> usize {
         let mut current = self.data;
-        loop {
-            let header = unsafe { &*(current as *const CpioHeader) };
-            if header.magic != *b"070701" {
-                break;
-            }
-            let namesize = u64::from_str_radix(
-                unsafe { core::str::from_utf8_unchecked(&header.namesize) },
-                16,
-            )
-            .unwrap();
-            let filesize = u64::from_str_radix(
-                unsafe { core::str::from_utf8_unchecked(&header.filesize) },
-                16,
-            )
-            .unwrap();
-            let name = unsafe {
-                core::str::from_utf8_unchecked(core::slice::from_raw_parts(
-                    current.add(110),
-                    namesize as usize,
-                ))
-            };
-
-            if name == "TRAILER!!!\0" {
+        while let Some((name, _, next)) = unsafe { entry(current) } {
+            current = next;
+            if name == TRAILER {
                 break;
             }
+        }
+        current as usize - self.data as usize
+    }
 
-            current = unsafe { current.add(110 + namesize as usize) };
-            if current as usize % 4 != 0 {
-                current = unsafe { current.add(4 - (current as usize % 4)) };
-            }
-
-            let mut found = true;
-            for i in 0..namesize as usize {
-                if name.as_bytes()[i] == 0 {
-                    break;
-                }
-                if name.as_bytes()[i] != filename.as_bytes()[i] {
-                    found = false;
-                    break;
-                }
-            }
-
-            if found {
-                return Some(unsafe { core::slice::from_raw_parts(current, filesize as usize) });
-            }
-
-            current = unsafe { current.add(filesize as usize) };
-            if current as usize % 4 != 0 {
-                current = unsafe { current.add(4 - (current as usize % 4)) };
-            }
+    pub fn print_file_list(&self) {
+        for (name, data) in self.files() {
+            stdio::println!("{} ({} bytes)", name, data.len());
         }
-        None
+    }
+
+    pub fn get_file(&self, filename: &str) -> Option<&[u8]> {
+        self.files()
+            .find(|(name, _)| *name == filename)
+            .map(|(_, data)| data)
     }
 }
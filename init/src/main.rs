@@ -0,0 +1,53 @@
+#![no_std]
+#![no_main]
+
+use core::time::Duration;
+use ulib::env::Args;
+use ulib::println;
+use ulib::process::{spawn, EXEC_FAILED};
+use ulib::{syscall, time};
+
+const SHELL: &str = "sh";
+
+// Pause between failed forks, and between polls for orphans without a shell
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+fn start_shell() -> u64 {
+    loop {
+        match spawn(SHELL, &[SHELL]) {
+            Ok(pid) => return pid,
+            Err(err) => println!("init: cannot fork {}: {}", SHELL, err),
+        }
+        let _ = time::sleep(RETRY_DELAY);
+    }
+}
+
+// PID 1: keep a shell running on the console and reap every orphan. It
+// never returns, the system cannot go on without it.
+#[no_mangle]
+fn main(_: Args) -> i32 {
+    println!("init: starting {}", SHELL);
+    let mut shell = Some(start_shell());
+    loop {
+        match syscall::wait() {
+            Ok((pid, status)) if Some(pid) == shell => {
+                if status == EXEC_FAILED as u64 {
+                    println!("init: {} not found in the initramfs", SHELL);
+                    shell = None;
+                    continue;
+                }
+                println!("init: {} exited with status {}, respawning", SHELL, status);
+                shell = Some(start_shell());
+            }
+            Ok(_) => {}
+            // Without a shell there may be nothing to wait for, idle
+            Err(_) if shell.is_none() => {
+                let _ = time::sleep(RETRY_DELAY);
+            }
+            Err(err) => {
+                println!("init: wait failed: {}", err);
+                shell = Some(start_shell());
+            }
+        }
+    }
+}
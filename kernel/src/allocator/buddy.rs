@@ -1,7 +1,8 @@
 use super::bump::BumpAllocator;
+use crate::mmu::{phys_to_virt, virt_to_phys};
 use alloc::{collections::BTreeSet, vec::Vec};
 use core::alloc::{GlobalAlloc, Layout};
-use stdio::{debug, println};
+use stdio::{debug, info, println, trace, warn};
 
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 enum BuddyState {
@@ -10,17 +11,19 @@ enum BuddyState {
     Allocated,
 }
 
-use super::config::{FRAME_SIZE, MEMORY_END, MEMORY_START};
-const NFRAME: usize = (MEMORY_END - MEMORY_START) as usize / FRAME_SIZE;
+use super::config::FRAME_SIZE;
+use super::memmap::MemoryMap;
 
 #[derive(Clone, Copy)]
 struct Frame {
     state: BuddyState,
 }
 
-const LAYER_COUNT: usize = 16;
+pub const LAYER_COUNT: usize = 16;
 
 pub struct BuddyAllocator {
+    // Address of frame 0, aligned to the largest block
+    base: u32,
     frames: Vec<Frame, BumpAllocator>,
     free_list: [BTreeSet<usize, BumpAllocator>; LAYER_COUNT],
     verbose: bool,
@@ -37,48 +40,73 @@ pub fn toggle_verbose() {
     });
 }
 
+pub fn set_verbose(verbose: bool) {
+    unsafe {
+        BUDDY_SYSTEM.verbose = verbose;
+    }
+}
+
 impl BuddyAllocator {
     pub const fn new() -> Self {
         const EMPTY: BTreeSet<usize, BumpAllocator> = BTreeSet::new_in(BumpAllocator);
         Self {
+            base: 0,
             frames: Vec::new_in(BumpAllocator),
             free_list: [EMPTY; LAYER_COUNT],
             verbose: false,
             initialized: false,
         }
     }
-    pub unsafe fn init(&mut self) {
-        println!("Initializing buddy allocator");
-        println!("Frame count: {}", NFRAME);
-        assert!(
-            MEMORY_START % (1 << LAYER_COUNT) as u32 == 0,
-            "Memory start 0x{:x} must be aligned to frame size 0x{:x}",
-            MEMORY_START,
-            FRAME_SIZE
-        );
+    // Cover the RAM of the map and reserve the holes in it along with the
+    // reserved regions
+    pub unsafe fn init(&mut self, map: &MemoryMap) {
+        info!("Initializing buddy allocator");
+        let block = (FRAME_SIZE << (LAYER_COUNT - 1)) as u32;
+        let start = map.usable.first().unwrap().start / block * block;
+        let end = map.usable.last().unwrap().end;
+        let nframe = (end - start) as usize / FRAME_SIZE;
+        debug!("Frame count: {}", nframe);
+        BUDDY_SYSTEM.base = start;
 
-        for _ in 0..NFRAME {
-            self.frames.push(Frame {
+        BUDDY_SYSTEM.frames.reserve_exact(nframe);
+        for _ in 0..nframe {
+            BUDDY_SYSTEM.frames.push(Frame {
                 state: BuddyState::Allocated,
             });
         }
-        for idx in 0..NFRAME {
+        for idx in 0..nframe {
             if let BuddyState::Owned(_) = BUDDY_SYSTEM.frames[idx].state {
                 continue;
             }
             for layer in (0..LAYER_COUNT).rev() {
-                if idx % (1 << layer) == 0 && (idx + (1 << layer) <= NFRAME) {
+                if idx % (1 << layer) == 0 && (idx + (1 << layer) <= nframe) {
                     BUDDY_SYSTEM.frames[idx].state = BuddyState::Head(layer);
                     BUDDY_SYSTEM.free_list[layer].insert(idx);
                     for i in 1..(1 << layer) {
                         BUDDY_SYSTEM.frames[idx + i].state = BuddyState::Owned(idx);
                     }
-                    println!("Initialized frame {} at layer {}", idx, layer);
+                    trace!("Initialized frame {} at layer {}", idx, layer);
                     break;
                 }
             }
         }
-        println!("Free list: {:?}", BUDDY_SYSTEM.free_list);
+        trace!("Free list: {:?}", BUDDY_SYSTEM.free_list);
+
+        let mut hole = start;
+        for usable in map.usable.iter() {
+            if hole < usable.start {
+                BUDDY_SYSTEM.reserve_by_addr_range(hole, usable.start);
+            }
+            hole = usable.end;
+        }
+        for r in map.reserved.iter() {
+            if !BUDDY_SYSTEM.reserve_by_addr_range(r.start, r.end) {
+                warn!(
+                    "Failed to reserve {} at 0x{:x} - 0x{:x}",
+                    r.name, r.start, r.end
+                );
+            }
+        }
     }
 
     pub unsafe fn print_info(&self) {
@@ -88,16 +116,27 @@ impl BuddyAllocator {
         }
     }
 
+    // Free blocks of 2^layer frames
+    pub fn free_blocks(&self, layer: usize) -> usize {
+        self.free_list[layer].len()
+    }
+
+    pub fn free_frames(&self) -> usize {
+        (0..LAYER_COUNT)
+            .map(|layer| self.free_blocks(layer) << layer)
+            .sum()
+    }
+
     fn faddr(&self, idx: usize) -> u32 {
-        MEMORY_START + (idx * FRAME_SIZE) as u32
+        self.base + (idx * FRAME_SIZE) as u32
     }
 
     fn idx(&self, addr: u32) -> usize {
-        (addr - MEMORY_START) as usize / FRAME_SIZE
+        (addr - self.base) as usize / FRAME_SIZE
     }
 
     unsafe fn alloc_frame(&mut self, idx: usize) {
-        assert!(idx < NFRAME);
+        assert!(idx < BUDDY_SYSTEM.frames.len());
         let layer = match BUDDY_SYSTEM.frames[idx].state {
             BuddyState::Head(l) => l,
             _ => panic!("Invalid state, expected Head"),
@@ -183,12 +222,12 @@ impl BuddyAllocator {
 
     unsafe fn free_by_layout(&mut self, ptr: *mut u8, size: usize, align: usize) {
         let addr = ptr as u32;
-        let idx = (addr - MEMORY_START) as usize / FRAME_SIZE;
+        let idx = BUDDY_SYSTEM.idx(addr);
         let mut layer = 0;
         if align < FRAME_SIZE {
             layer = 0;
         } else {
-            while (1 << layer) < align {
+            while (1 << layer) * FRAME_SIZE < align {
                 layer += 1;
             }
         }
@@ -198,13 +237,24 @@ impl BuddyAllocator {
         if BUDDY_SYSTEM.verbose {
             println!("Free frame {} at layer {}", idx, layer);
         }
+        #[cfg(feature = "heap-debug")]
+        for i in idx..idx + (1 << layer) {
+            if BUDDY_SYSTEM.frames[i].state != BuddyState::Allocated {
+                panic!(
+                    "Double free of frame {} at 0x{:x}, state {:?}",
+                    i,
+                    BUDDY_SYSTEM.faddr(i),
+                    BUDDY_SYSTEM.frames[i].state
+                );
+            }
+        }
         BUDDY_SYSTEM.free_by_idx(idx, layer);
     }
 
     pub unsafe fn free_by_idx(&mut self, mut idx: usize, mut layer: usize) {
         loop {
             let buddy = idx ^ (1 << layer);
-            if buddy >= NFRAME {
+            if buddy >= BUDDY_SYSTEM.frames.len() {
                 println!("Buddy out of range");
                 break;
             }
@@ -243,7 +293,7 @@ impl BuddyAllocator {
     }
 
     pub unsafe fn get_frame(&mut self, layer: usize, idx: usize) -> Option<usize> {
-        assert!(idx < NFRAME, "Frame index out of range");
+        assert!(idx < BUDDY_SYSTEM.frames.len(), "Frame index out of range");
         assert!(
             BUDDY_SYSTEM.frames[idx].state != BuddyState::Allocated,
             "Frame is allocated"
@@ -268,11 +318,15 @@ impl BuddyAllocator {
     }
 
     pub unsafe fn reserve_frame(&mut self, idx: usize) -> bool {
-        if idx >= NFRAME {
-            debug!("Invalid frame index {} > {}", idx, NFRAME);
+        if idx >= BUDDY_SYSTEM.frames.len() {
+            debug!(
+                "Invalid frame index {} > {}",
+                idx,
+                BUDDY_SYSTEM.frames.len()
+            );
         }
         if BUDDY_SYSTEM.frames[idx].state == BuddyState::Allocated {
-            println!("Frame {} is already allocated", idx);
+            warn!("Frame {} is already allocated", idx);
             return false;
         }
         BUDDY_SYSTEM.get_frame(0, idx);
@@ -299,6 +353,7 @@ impl BuddyAllocator {
     }
 }
 
+// Blocks are handed out and taken back as pointers into the linear map
 unsafe impl GlobalAlloc for BuddyAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let size = layout.size();
@@ -316,16 +371,20 @@ unsafe impl GlobalAlloc for BuddyAllocator {
             if BUDDY_SYSTEM.verbose {
                 println!("Free list: {:?}", BUDDY_SYSTEM.free_list);
             }
-            addr as *mut u8
+            phys_to_virt(addr as u64) as *mut u8
         } else {
-            panic!("Out of memory");
+            warn!(
+                "BuddyAllocator: out of memory for size {} align {}",
+                size, align
+            );
+            core::ptr::null_mut()
         }
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         let size = layout.size();
         let align = layout.align();
-        BUDDY_SYSTEM.free_by_layout(ptr, size, align);
+        BUDDY_SYSTEM.free_by_layout(virt_to_phys(ptr as u64) as *mut u8, size, align);
     }
 }
 
@@ -1,7 +1,8 @@
-use super::config::{BUMP_END_ADDR, BUMP_START_ADDR};
+use super::config::EARLY_HEAP_SIZE;
+use crate::mmu::config::KERNEL_BASE;
+use crate::mmu::phys_to_virt;
 use core::alloc::{AllocError, Allocator, GlobalAlloc, Layout};
 use core::ptr::NonNull;
-#[allow(unused_imports)]
 use stdio::debug;
 use stdio::println;
 
@@ -10,7 +11,38 @@ pub struct BumpAllocator;
 
 static mut VERBOSE: bool = false;
 
-static mut CUR: u32 = BUMP_START_ADDR;
+#[repr(C, align(4096))]
+struct EarlyHeap([u8; EARLY_HEAP_SIZE]);
+
+// Serves the allocations made before the memory map is known, such as the
+// device tree, from the kernel image
+static mut EARLY_HEAP: EarlyHeap = EarlyHeap([0; EARLY_HEAP_SIZE]);
+
+static mut CUR: u32 = 0;
+static mut END: u32 = 0;
+// Start of the region set by set_region, 0 while in the early heap
+static mut REGION_START: u32 = 0;
+
+fn early_heap() -> (u32, u32) {
+    let start = (core::ptr::addr_of!(EARLY_HEAP) as u64 - KERNEL_BASE) as u32;
+    (start, start + EARLY_HEAP_SIZE as u32)
+}
+
+// Whether the physical address addr was handed out by the bump allocator
+pub fn contains(addr: u32) -> bool {
+    let (early_start, early_end) = early_heap();
+    unsafe { (early_start..early_end).contains(&addr) || (REGION_START..END).contains(&addr) }
+}
+
+// Continue allocating from [start, end), earlier allocations stay valid
+pub fn set_region(start: u32, end: u32) {
+    unsafe {
+        CUR = start;
+        END = end;
+        REGION_START = start;
+    }
+    debug!("BumpAllocator: moved to 0x{:x} - 0x{:x}", start, end);
+}
 
 #[allow(dead_code)]
 pub fn toggle_verbose() {
@@ -22,19 +54,24 @@ pub fn toggle_verbose() {
 
 unsafe impl GlobalAlloc for BumpAllocator {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if END == 0 {
+            (CUR, END) = early_heap();
+        }
         let size = layout.size() as u32;
         let align = layout.align() as u32;
         let mut ret = CUR;
         ret = (ret + align - 1) & !(align - 1);
+        if ret + size > END {
+            return core::ptr::null_mut();
+        }
         CUR = ret + size;
-        assert!(CUR < BUMP_END_ADDR, "Bump allocator out of memory!");
         if unsafe { VERBOSE } {
             debug!(
                 "BumpAllocator: alloc 0x{:x} size 0x{:x} align 0x{:x}",
                 ret, size, align
             );
         }
-        ret as *mut u8
+        phys_to_virt(ret as u64) as *mut u8
     }
 
     unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {
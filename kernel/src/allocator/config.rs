@@ -1,6 +1,12 @@
-pub const BUMP_START_ADDR: u32 = 0x1000_0000;
-pub const BUMP_END_ADDR: u32 = 0x1100_0000;
+// The boot allocator starts in an arena in the kernel image and moves to a
+// region of BUMP_SIZE bytes once the memory map is known
+pub const EARLY_HEAP_SIZE: usize = 0x10_0000;
+pub const BUMP_SIZE: u32 = 0x100_0000;
 
-pub const MEMORY_START: u32 = 0;
-pub const MEMORY_END: u32 = 0x3c00_0000;
 pub const FRAME_SIZE: usize = 0x1000;
+
+// Size classes of the kernel heap, each a power of two. Larger allocations
+// take whole blocks from the buddy system.
+pub const SLAB_SIZES: [usize; 8] = [16, 32, 64, 128, 256, 512, 1024, 2048];
+// A slab spans as many frames as it takes to hold this many objects
+pub const SLAB_MIN_OBJECTS: usize = 4;
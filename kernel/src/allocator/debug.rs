@@ -0,0 +1,190 @@
+// Heap debugging, built with the heap-debug feature. Every heap allocation
+// is padded with redzones that are checked when it is freed, freed memory
+// is poisoned, slab objects are checked when handed out again, and
+// outstanding allocations are recorded with the return addresses of their
+// callers.
+// Build with frame pointers (make HEAP_DEBUG=1) for the callers to be found.
+
+use core::alloc::Layout;
+use core::arch::asm;
+
+pub const REDZONE: usize = 16;
+const REDZONE_BYTE: u8 = 0xfb;
+const POISON_BYTE: u8 = 0x6b;
+// The first word of a free slab object holds its free list link, the rest of
+// a poisoned front redzone marks the block freed
+const MARK_OFFSET: usize = core::mem::size_of::<usize>();
+
+// Return addresses kept per allocation, after skipping the allocator's own
+// frames
+pub const CALLER_DEPTH: usize = 4;
+const SKIP_FRAMES: usize = 2;
+const MAX_RECORDS: usize = 4096;
+
+#[derive(Clone, Copy)]
+pub struct Record {
+    pub ptr: usize,
+    pub size: usize,
+    pub callers: [usize; CALLER_DEPTH],
+}
+
+const EMPTY: Record = Record {
+    ptr: 0,
+    size: 0,
+    callers: [0; CALLER_DEPTH],
+};
+
+struct Records {
+    records: [Record; MAX_RECORDS],
+    len: usize,
+    // Live allocations that did not fit in the table
+    untracked: usize,
+}
+
+static mut RECORDS: Records = Records {
+    records: [EMPTY; MAX_RECORDS],
+    len: 0,
+    untracked: 0,
+};
+
+fn records() -> &'static mut Records {
+    unsafe { &mut *core::ptr::addr_of_mut!(RECORDS) }
+}
+
+// Padding in front of the allocation, keeping it aligned
+fn front(layout: &Layout) -> usize {
+    REDZONE.max(layout.align())
+}
+
+// The layout to allocate for a request of the given layout
+pub fn outer_layout(layout: Layout) -> Layout {
+    Layout::from_size_align(front(&layout) + layout.size() + REDZONE, layout.align()).unwrap()
+}
+
+// Walk the frame records, each holding the caller's frame pointer followed
+// by the return address. Stacks grow down, so a caller's frame is higher.
+#[inline(always)]
+fn callers() -> [usize; CALLER_DEPTH] {
+    let mut ret = [0; CALLER_DEPTH];
+    let mut fp: usize;
+    unsafe {
+        asm!("mov {}, x29", out(reg) fp);
+    }
+    for i in 0..SKIP_FRAMES + CALLER_DEPTH {
+        if fp == 0 || fp % 16 != 0 {
+            break;
+        }
+        let (next, lr) = unsafe { (*(fp as *const usize), *(fp as *const usize).add(1)) };
+        if i >= SKIP_FRAMES {
+            ret[i - SKIP_FRAMES] = lr;
+        }
+        if next <= fp {
+            break;
+        }
+        fp = next;
+    }
+    ret
+}
+
+// Fill the redzones around an allocation at outer and record it, returning
+// the pointer to hand out
+#[inline(always)]
+pub unsafe fn on_alloc(outer: *mut u8, layout: Layout) -> *mut u8 {
+    let ptr = outer.add(front(&layout));
+    core::ptr::write_bytes(outer, REDZONE_BYTE, front(&layout));
+    core::ptr::write_bytes(ptr.add(layout.size()), REDZONE_BYTE, REDZONE);
+    let records = records();
+    if records.len == MAX_RECORDS {
+        records.untracked += 1;
+    } else {
+        records.records[records.len] = Record {
+            ptr: ptr as usize,
+            size: layout.size(),
+            callers: callers(),
+        };
+        records.len += 1;
+    }
+    ptr
+}
+
+fn check_redzone(ptr: *mut u8, zone: *const u8, len: usize, side: &str) {
+    let zone = unsafe { core::slice::from_raw_parts(zone, len) };
+    if let Some(offset) = zone.iter().position(|&b| b != REDZONE_BYTE) {
+        panic!(
+            "Heap corruption: {} redzone of 0x{:x} overwritten at byte {}",
+            side, ptr as usize, offset
+        );
+    }
+}
+
+fn is_freed(outer: *const u8, front: usize) -> bool {
+    let mark = unsafe { core::slice::from_raw_parts(outer.add(MARK_OFFSET), front - MARK_OFFSET) };
+    mark.iter().all(|&b| b == POISON_BYTE)
+}
+
+// Check the redzones of a freed allocation, forget it and mark it freed,
+// returning the pointer the allocator handed out
+pub unsafe fn on_dealloc(ptr: *mut u8, layout: Layout) -> *mut u8 {
+    let outer = ptr.sub(front(&layout));
+    if is_freed(outer, front(&layout)) {
+        panic!("Double free of 0x{:x}", ptr as usize);
+    }
+    let records = records();
+    match records.records[..records.len]
+        .iter()
+        .position(|record| record.ptr == ptr as usize)
+    {
+        Some(idx) => {
+            let record = records.records[idx];
+            if record.size != layout.size() {
+                panic!(
+                    "Heap free of 0x{:x} with size {}, allocated with size {}",
+                    ptr as usize,
+                    layout.size(),
+                    record.size
+                );
+            }
+            records.len -= 1;
+            records.records[idx] = records.records[records.len];
+        }
+        // Only allocations that did not fit in the table may be missing
+        None if records.untracked == 0 => {
+            panic!("Invalid free of 0x{:x}", ptr as usize);
+        }
+        None => records.untracked -= 1,
+    }
+    check_redzone(ptr, outer, front(&layout), "front");
+    check_redzone(ptr, ptr.add(layout.size()), REDZONE, "back");
+    core::ptr::write_bytes(outer, POISON_BYTE, front(&layout));
+    outer
+}
+
+// Poison a free object or block, past the free list link at its start
+pub unsafe fn poison(obj: *mut u8, size: usize, link: usize) {
+    core::ptr::write_bytes(obj.add(link), POISON_BYTE, size - link);
+}
+
+// Check that a free object was not written to since it was poisoned
+pub unsafe fn check_poison(obj: *mut u8, size: usize, link: usize) {
+    let bytes = core::slice::from_raw_parts(obj.add(link), size - link);
+    if let Some(offset) = bytes.iter().position(|&b| b != POISON_BYTE) {
+        panic!(
+            "Heap corruption: free object 0x{:x} written at byte {} after free",
+            obj as usize,
+            link + offset
+        );
+    }
+}
+
+// Copy the outstanding allocations into out, returning how many there are
+// and how many were not recorded
+pub fn snapshot(out: &mut [Record]) -> (usize, usize) {
+    let records = records();
+    let len = records.len.min(out.len());
+    out[..len].copy_from_slice(&records.records[..len]);
+    (len, records.untracked)
+}
+
+pub fn outstanding() -> usize {
+    records().len
+}
@@ -0,0 +1,81 @@
+// A snapshot of the physical memory accounting: frames from the memory map
+// and the buddy system, the kernel heap and the page tables.
+
+use super::buddy::{BUDDY_SYSTEM, LAYER_COUNT};
+use super::config::FRAME_SIZE;
+use super::memmap;
+use super::slab::{self, CacheStats};
+use alloc::vec::Vec;
+
+pub struct MemInfo {
+    // In frames
+    pub total: usize,
+    pub free: usize,
+    pub reserved: usize,
+    // Free blocks of 2^layer frames
+    pub free_blocks: [usize; LAYER_COUNT],
+    pub caches: Vec<CacheStats>,
+    // Bytes taken from the buddy system by allocations too large for a slab
+    pub large_bytes: usize,
+    pub page_tables: usize,
+}
+
+impl MemInfo {
+    // Frames neither free nor reserved at boot
+    pub fn used(&self) -> usize {
+        self.total - self.free - self.reserved
+    }
+
+    pub fn slab_bytes(&self) -> usize {
+        self.caches
+            .iter()
+            .map(|cache| cache.slabs * cache.slab_size)
+            .sum()
+    }
+
+    pub fn slab_used_bytes(&self) -> usize {
+        self.caches
+            .iter()
+            .map(|cache| cache.in_use * cache.size)
+            .sum()
+    }
+
+    // Share of the free memory, in thousandths, that is in blocks too small
+    // for an allocation of 2^layer frames. 0 means no fragmentation.
+    pub fn unusable_index(&self, layer: usize) -> usize {
+        if self.free == 0 {
+            return 0;
+        }
+        let usable: usize = (layer..LAYER_COUNT).map(|l| self.free_blocks[l] << l).sum();
+        (self.free - usable) * 1000 / self.free
+    }
+}
+
+pub fn bytes(frames: usize) -> usize {
+    frames * FRAME_SIZE
+}
+
+pub fn snapshot() -> MemInfo {
+    let map = memmap::get();
+    let frames = |regions: &[memmap::Region]| {
+        regions
+            .iter()
+            .map(|r| r.size() as usize / FRAME_SIZE)
+            .sum::<usize>()
+    };
+    let mut free_blocks = [0; LAYER_COUNT];
+    for (layer, count) in free_blocks.iter_mut().enumerate() {
+        *count = unsafe { BUDDY_SYSTEM.free_blocks(layer) };
+    }
+    let mut caches = Vec::new();
+    slab::for_each_cache(|cache| caches.push(*cache));
+    MemInfo {
+        total: frames(&map.usable),
+        free: unsafe { BUDDY_SYSTEM.free_frames() },
+        reserved: frames(&map.reserved),
+        free_blocks,
+        caches,
+        large_bytes: slab::large_bytes(),
+        page_tables: crate::mmu::table_pages(),
+    }
+}
@@ -0,0 +1,240 @@
+// The physical memory map built at boot. RAM comes from the DTB /memory nodes,
+// cut down to the ARM's share reported by the mailbox, and the reservations
+// cover whatever is in use before the buddy allocator takes over.
+
+use super::config::{BUMP_SIZE, FRAME_SIZE};
+use crate::dtb;
+use crate::mmu::config::{GUARD_SIZE, KERNEL_BASE, KERNEL_STACK_SIZE, LINEAR_MAP_END};
+use crate::mmu::phys_to_virt;
+use alloc::vec::Vec;
+use filesystem::cpio::CpioArchive;
+use stdio::{debug, info};
+
+extern "C" {
+    static _start: u8;
+    static __kernel_end: u8;
+}
+
+// Below _start: the boot page tables from 0x1000 and, right under the
+// kernel, the boot stack with the DTB pointer left by the bootloader
+const BOOT_TABLES_START: u32 = 0x1000;
+const BOOT_TABLES_END: u32 = 0x1_0000;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Region {
+    pub start: u32,
+    pub end: u32,
+    pub name: &'static str,
+}
+
+impl Region {
+    pub fn size(&self) -> u32 {
+        self.end - self.start
+    }
+}
+
+pub struct MemoryMap {
+    // Sorted and disjoint
+    pub usable: Vec<Region>,
+    // Sorted and disjoint, frame aligned and within the usable RAM
+    pub reserved: Vec<Region>,
+}
+
+static mut MEMORY_MAP: Option<MemoryMap> = None;
+
+pub fn get() -> &'static MemoryMap {
+    unsafe {
+        (*core::ptr::addr_of!(MEMORY_MAP))
+            .as_ref()
+            .expect("Memory map not built")
+    }
+}
+
+fn align_down(addr: u32) -> u32 {
+    addr & !(FRAME_SIZE as u32 - 1)
+}
+
+fn align_up(addr: u32) -> u32 {
+    align_down(addr + FRAME_SIZE as u32 - 1)
+}
+
+// Physical address of a kernel image symbol
+fn image_addr(sym: &u8) -> u32 {
+    (sym as *const u8 as u64 - KERNEL_BASE) as u32
+}
+
+// Clamp a 64-bit (addr, size) pair to the 32-bit physical space
+fn region(addr: u64, size: u64, name: &'static str) -> Option<Region> {
+    let end = addr.saturating_add(size).min(u32::MAX as u64);
+    if addr >= end {
+        return None;
+    }
+    Some(Region {
+        start: addr as u32,
+        end: end as u32,
+        name,
+    })
+}
+
+fn ram_regions() -> Vec<Region> {
+    let tree = dtb::get();
+    let mut ram: Vec<Region> = tree
+        .root()
+        .children()
+        .filter(|node| {
+            node.base_name() == "memory"
+                || node.property("device_type").and_then(|p| p.as_str()) == Some("memory")
+        })
+        .flat_map(|node| node.regions())
+        .filter_map(|(addr, size)| region(addr, size, "ram"))
+        .collect();
+
+    // The firmware splits the RAM with the GPU and may leave /memory empty,
+    // the mailbox knows the ARM's share
+    let (arm_base, arm_size) = driver::mailbox::get_arm_memory();
+    debug!("ARM memory: {:#x} - {:#x}", arm_base, arm_base + arm_size);
+    let arm = region(arm_base as u64, arm_size as u64, "ram");
+    ram = match arm {
+        None => ram,
+        Some(arm) if ram.is_empty() => alloc::vec![arm],
+        Some(arm) => ram
+            .into_iter()
+            .filter_map(|r| {
+                let start = r.start.max(arm.start);
+                let end = r.end.min(arm.end);
+                (start < end).then_some(Region { start, end, ..r })
+            })
+            .collect(),
+    };
+    // The kernel reaches RAM through the linear map only
+    for r in ram.iter_mut() {
+        r.start = align_up(r.start);
+        r.end = align_down(r.end.min(LINEAR_MAP_END as u32));
+    }
+    ram.retain(|r| r.start < r.end);
+    ram.sort_by_key(|r| r.start);
+    ram
+}
+
+fn boot_reservations() -> Vec<Region> {
+    let kernel_start = image_addr(unsafe { &_start });
+    let kernel_end = image_addr(unsafe { &__kernel_end });
+    let mut reserved = alloc::vec![
+        Region {
+            start: BOOT_TABLES_START,
+            end: BOOT_TABLES_END,
+            name: "boot page tables",
+        },
+        // Along with its guard page, which is not mapped
+        Region {
+            start: kernel_start - (KERNEL_STACK_SIZE + GUARD_SIZE) as u32,
+            end: kernel_start,
+            name: "boot stack",
+        },
+        Region {
+            start: kernel_start,
+            end: kernel_end,
+            name: "kernel",
+        },
+    ];
+
+    let (dtb_addr, header) = dtb::get_dtb_addr();
+    reserved.push(Region {
+        start: dtb_addr,
+        end: dtb_addr + header.totalsize,
+        name: "dtb",
+    });
+
+    let initrd_start = dtb::get_initrd_start();
+    // Without linux,initrd-end the archive itself tells where it ends
+    let initrd_end = dtb::get_initrd_end().unwrap_or_else(|| {
+        let archive = CpioArchive::load(phys_to_virt(initrd_start as u64) as *const u8);
+        initrd_start + archive.size() as u32
+    });
+    reserved.push(Region {
+        start: initrd_start,
+        end: initrd_end,
+        name: "initramfs",
+    });
+
+    for (addr, size) in dtb::get_reserved_memory() {
+        reserved.extend(region(addr, size, "firmware"));
+    }
+    reserved
+}
+
+// Align the reservations to frames, clip them to the RAM and merge the
+// overlapping ones, keeping the name of the first
+fn normalize(mut reserved: Vec<Region>, ram: &[Region]) -> Vec<Region> {
+    let mut clipped = Vec::new();
+    for r in reserved.iter_mut() {
+        r.start = align_down(r.start);
+        r.end = align_up(r.end);
+        for usable in ram {
+            let start = r.start.max(usable.start);
+            let end = r.end.min(usable.end);
+            if start < end {
+                clipped.push(Region { start, end, ..*r });
+            }
+        }
+    }
+    clipped.sort_by_key(|r| r.start);
+    let mut merged: Vec<Region> = Vec::new();
+    for r in clipped {
+        match merged.last_mut() {
+            Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+            _ => merged.push(r),
+        }
+    }
+    merged
+}
+
+// The lowest free range of the given size, frame aligned
+fn find_free(ram: &[Region], reserved: &[Region], size: u32) -> Option<u32> {
+    for usable in ram {
+        let mut start = usable.start;
+        for r in reserved.iter() {
+            if r.end <= start || r.start >= usable.end {
+                continue;
+            }
+            if r.start >= start && r.start - start >= size {
+                return Some(start);
+            }
+            start = start.max(r.end);
+        }
+        if usable.end > start && usable.end - start >= size {
+            return Some(start);
+        }
+    }
+    None
+}
+
+pub fn init() {
+    let ram = ram_regions();
+    assert!(!ram.is_empty(), "No usable memory found");
+    let mut reserved = normalize(boot_reservations(), &ram);
+
+    // The boot allocator moves out of its arena in the kernel image into
+    // RAM that nothing else claims
+    let bump = find_free(&ram, &reserved, BUMP_SIZE).expect("No room for the boot allocator");
+    super::bump::set_region(bump, bump + BUMP_SIZE);
+    reserved.push(Region {
+        start: bump,
+        end: bump + BUMP_SIZE,
+        name: "boot allocator",
+    });
+    reserved.sort_by_key(|r| r.start);
+
+    for r in ram.iter() {
+        info!("RAM {:#010x} - {:#010x}", r.start, r.end);
+    }
+    for r in reserved.iter() {
+        debug!("Reserved {:#010x} - {:#010x} {}", r.start, r.end, r.name);
+    }
+    unsafe {
+        MEMORY_MAP = Some(MemoryMap {
+            usable: ram,
+            reserved,
+        });
+    }
+}
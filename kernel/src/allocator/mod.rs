@@ -1,5 +1,9 @@
 pub mod buddy;
 mod bump;
 pub mod config;
-mod dynamic;
+#[cfg(feature = "heap-debug")]
+pub mod debug;
+pub mod meminfo;
+pub mod memmap;
+pub mod slab;
 pub mod utils;
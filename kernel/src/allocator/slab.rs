@@ -0,0 +1,308 @@
+// The kernel heap. Small allocations are served by one cache per size class,
+// each a list of slabs taken from the buddy system. A slab keeps its header
+// and the free list of its objects in its own pages, and goes back to the
+// buddy system once all of its objects are freed. Larger allocations go to
+// the buddy system directly.
+
+use super::buddy::BUDDY_SYSTEM;
+use super::bump::{self, BumpAllocator};
+use super::config::{FRAME_SIZE, SLAB_MIN_OBJECTS, SLAB_SIZES};
+#[cfg(feature = "heap-debug")]
+use super::debug;
+use crate::mmu::virt_to_phys;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::null_mut;
+use stdio::{debug, println};
+
+#[global_allocator]
+pub static mut SLAB_ALLOCATOR: SlabAllocator = SlabAllocator::new();
+
+#[allow(dead_code)]
+pub fn toggle_verbose() {
+    unsafe {
+        SLAB_ALLOCATOR.verbose = !SLAB_ALLOCATOR.verbose;
+    }
+    println!("SlabAllocator verbose: {}", unsafe {
+        SLAB_ALLOCATOR.verbose
+    });
+}
+
+// Links a free object to the next one of its slab
+struct FreeObject {
+    next: *mut FreeObject,
+}
+
+#[cfg(feature = "heap-debug")]
+const LINK_SIZE: usize = core::mem::size_of::<FreeObject>();
+
+// At the start of every slab, the objects follow it
+struct Slab {
+    prev: *mut Slab,
+    next: *mut Slab,
+    free: *mut FreeObject,
+    in_use: usize,
+}
+
+struct Cache {
+    size: usize,
+    // Bytes per slab, a power-of-two number of frames
+    slab_size: usize,
+    // Slabs with at least one free object, full ones are off the list
+    partial: *mut Slab,
+    slabs: usize,
+    in_use: usize,
+    allocs: u64,
+    frees: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct CacheStats {
+    pub size: usize,
+    pub slab_size: usize,
+    pub slabs: usize,
+    pub objects: usize,
+    pub in_use: usize,
+    pub allocs: u64,
+    pub frees: u64,
+}
+
+pub struct SlabAllocator {
+    caches: [Cache; SLAB_SIZES.len()],
+    // Bytes handed out straight from the buddy system
+    large_bytes: usize,
+    verbose: bool,
+}
+
+const fn header_size(size: usize) -> usize {
+    let header = core::mem::size_of::<Slab>();
+    (header + size - 1) / size * size
+}
+
+const fn slab_size(size: usize) -> usize {
+    let mut slab_size = FRAME_SIZE;
+    while (slab_size - header_size(size)) / size < SLAB_MIN_OBJECTS {
+        slab_size *= 2;
+    }
+    slab_size
+}
+
+const fn objects_per_slab(size: usize, slab_size: usize) -> usize {
+    (slab_size - header_size(size)) / size
+}
+
+impl Cache {
+    const fn new(size: usize) -> Self {
+        assert!(size.is_power_of_two() && size >= core::mem::size_of::<FreeObject>());
+        Cache {
+            size,
+            slab_size: slab_size(size),
+            partial: null_mut(),
+            slabs: 0,
+            in_use: 0,
+            allocs: 0,
+            frees: 0,
+        }
+    }
+
+    fn objects_per_slab(&self) -> usize {
+        objects_per_slab(self.size, self.slab_size)
+    }
+
+    fn slab_layout(&self) -> Layout {
+        Layout::from_size_align(self.slab_size, self.slab_size).unwrap()
+    }
+
+    unsafe fn push_partial(&mut self, slab: *mut Slab) {
+        (*slab).prev = null_mut();
+        (*slab).next = self.partial;
+        if !self.partial.is_null() {
+            (*self.partial).prev = slab;
+        }
+        self.partial = slab;
+    }
+
+    unsafe fn unlink(&mut self, slab: *mut Slab) {
+        if (*slab).prev.is_null() {
+            self.partial = (*slab).next;
+        } else {
+            (*(*slab).prev).next = (*slab).next;
+        }
+        if !(*slab).next.is_null() {
+            (*(*slab).next).prev = (*slab).prev;
+        }
+    }
+
+    unsafe fn grow(&mut self) -> bool {
+        let slab = BUDDY_SYSTEM.alloc(self.slab_layout()) as *mut Slab;
+        if slab.is_null() {
+            return false;
+        }
+        let first = slab as usize + header_size(self.size);
+        let mut free = null_mut();
+        for i in (0..self.objects_per_slab()).rev() {
+            let obj = (first + i * self.size) as *mut FreeObject;
+            #[cfg(feature = "heap-debug")]
+            debug::poison(obj as *mut u8, self.size, LINK_SIZE);
+            (*obj).next = free;
+            free = obj;
+        }
+        slab.write(Slab {
+            prev: null_mut(),
+            next: null_mut(),
+            free,
+            in_use: 0,
+        });
+        self.push_partial(slab);
+        self.slabs += 1;
+        true
+    }
+
+    unsafe fn alloc(&mut self) -> *mut u8 {
+        if self.partial.is_null() && !self.grow() {
+            return null_mut();
+        }
+        let slab = self.partial;
+        let obj = (*slab).free;
+        #[cfg(feature = "heap-debug")]
+        debug::check_poison(obj as *mut u8, self.size, LINK_SIZE);
+        (*slab).free = (*obj).next;
+        (*slab).in_use += 1;
+        if (*slab).free.is_null() {
+            self.unlink(slab);
+        }
+        self.in_use += 1;
+        self.allocs += 1;
+        obj as *mut u8
+    }
+
+    unsafe fn dealloc(&mut self, ptr: *mut u8) {
+        let slab = (ptr as usize & !(self.slab_size - 1)) as *mut Slab;
+        let obj = ptr as *mut FreeObject;
+        let was_full = (*slab).free.is_null();
+        #[cfg(feature = "heap-debug")]
+        debug::poison(ptr, self.size, LINK_SIZE);
+        (*obj).next = (*slab).free;
+        (*slab).free = obj;
+        (*slab).in_use -= 1;
+        if was_full {
+            self.push_partial(slab);
+        }
+        self.in_use -= 1;
+        self.frees += 1;
+        // Keep the last slab so a single object going back and forth does
+        // not take a page from the buddy system every time
+        let last = (*slab).prev.is_null() && (*slab).next.is_null();
+        if (*slab).in_use == 0 && !last {
+            self.unlink(slab);
+            self.slabs -= 1;
+            BUDDY_SYSTEM.dealloc(slab as *mut u8, self.slab_layout());
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            size: self.size,
+            slab_size: self.slab_size,
+            slabs: self.slabs,
+            objects: self.slabs * self.objects_per_slab(),
+            in_use: self.in_use,
+            allocs: self.allocs,
+            frees: self.frees,
+        }
+    }
+}
+
+impl SlabAllocator {
+    const fn new() -> Self {
+        let mut caches = [const { Cache::new(SLAB_SIZES[0]) }; SLAB_SIZES.len()];
+        let mut i = 1;
+        while i < SLAB_SIZES.len() {
+            caches[i] = Cache::new(SLAB_SIZES[i]);
+            i += 1;
+        }
+        SlabAllocator {
+            caches,
+            large_bytes: 0,
+            verbose: false,
+        }
+    }
+
+    // The smallest size class that fits the layout, objects are aligned to
+    // their size
+    fn cache_index(layout: &Layout) -> Option<usize> {
+        let size = layout.size().max(layout.align());
+        SLAB_SIZES.iter().position(|&class| class >= size)
+    }
+}
+
+pub fn for_each_cache(mut f: impl FnMut(&CacheStats)) {
+    for cache in unsafe { SLAB_ALLOCATOR.caches.iter() } {
+        f(&cache.stats());
+    }
+}
+
+pub fn large_bytes() -> usize {
+    unsafe { SLAB_ALLOCATOR.large_bytes }
+}
+
+unsafe impl GlobalAlloc for SlabAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if !BUDDY_SYSTEM.initialized {
+            return BumpAllocator.alloc(layout);
+        }
+        #[cfg(feature = "heap-debug")]
+        let (layout, inner) = (debug::outer_layout(layout), layout);
+        let ptr = match SlabAllocator::cache_index(&layout) {
+            Some(idx) => SLAB_ALLOCATOR.caches[idx].alloc(),
+            None => {
+                let ptr = BUDDY_SYSTEM.alloc(layout);
+                if !ptr.is_null() {
+                    SLAB_ALLOCATOR.large_bytes += layout.size();
+                }
+                ptr
+            }
+        };
+        if ptr.is_null() {
+            return ptr;
+        }
+        #[cfg(feature = "heap-debug")]
+        let (ptr, layout) = (debug::on_alloc(ptr, inner), inner);
+        if SLAB_ALLOCATOR.verbose {
+            debug!(
+                "SlabAllocator: alloc 0x{:x} size {} align {}",
+                ptr as usize,
+                layout.size(),
+                layout.align()
+            );
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // Memory from the boot allocator is never reclaimed
+        if bump::contains(virt_to_phys(ptr as u64) as u32) {
+            return;
+        }
+        #[cfg(feature = "heap-debug")]
+        let (ptr, layout) = (debug::on_dealloc(ptr, layout), debug::outer_layout(layout));
+        if SLAB_ALLOCATOR.verbose {
+            debug!(
+                "SlabAllocator: dealloc 0x{:x} size {} align {}",
+                ptr as usize,
+                layout.size(),
+                layout.align()
+            );
+        }
+        match SlabAllocator::cache_index(&layout) {
+            Some(idx) => SLAB_ALLOCATOR.caches[idx].dealloc(ptr),
+            None => {
+                SLAB_ALLOCATOR.large_bytes -= layout.size();
+                // The frames may go to anyone next, so the poison is not
+                // checked, it only makes reads after the free stand out
+                #[cfg(feature = "heap-debug")]
+                debug::poison(ptr, layout.size(), 0);
+                BUDDY_SYSTEM.dealloc(ptr, layout)
+            }
+        }
+    }
+}
@@ -3,4 +3,4 @@ pub use super::buddy::toggle_verbose as toggle_buddy_verbose;
 #[allow(unused_imports)]
 pub use super::bump::toggle_verbose as toggle_bump_verbose;
 #[allow(unused_imports)]
-pub use super::dynamic::toggle_verbose as toggle_dynamic_verbose;
+pub use super::slab::toggle_verbose as toggle_slab_verbose;
@@ -0,0 +1,97 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug)]
+pub enum ParseError {
+    UnterminatedQuote(char),
+    TrailingBackslash,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote(quote) => write!(f, "unterminated {} quote", quote),
+            ParseError::TrailingBackslash => write!(f, "trailing backslash"),
+        }
+    }
+}
+
+fn escape(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        c => c,
+    }
+}
+
+// Split a command line into the argv of each pipeline stage. Single quotes
+// are literal, double quotes and bare words understand backslash escapes,
+// and an unquoted `|` starts a new stage.
+pub fn parse(line: &str) -> Result<Vec<Vec<String>>, ParseError> {
+    let mut stages = Vec::new();
+    let mut argv = Vec::new();
+    let mut arg = String::new();
+    // Whether arg holds a word, which may be empty like ''
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\r' | '\n' => {
+                if in_word {
+                    argv.push(core::mem::take(&mut arg));
+                    in_word = false;
+                }
+            }
+            '|' => {
+                if in_word {
+                    argv.push(core::mem::take(&mut arg));
+                    in_word = false;
+                }
+                stages.push(core::mem::take(&mut argv));
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => arg.push(escape(c)),
+                            None => return Err(ParseError::UnterminatedQuote('"')),
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => arg.push(escape(c)),
+                    None => return Err(ParseError::TrailingBackslash),
+                }
+            }
+            c => {
+                in_word = true;
+                arg.push(c);
+            }
+        }
+    }
+    if in_word {
+        argv.push(arg);
+    }
+    stages.push(argv);
+    Ok(stages)
+}
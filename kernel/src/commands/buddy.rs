@@ -1,5 +1,7 @@
+use super::Command;
 use crate::allocator::buddy::BUDDY_SYSTEM;
 use crate::allocator::utils::toggle_buddy_verbose;
+use alloc::string::String;
 use alloc::vec::Vec;
 use stdio::gets;
 use stdio::print;
@@ -7,13 +9,29 @@ use stdio::println;
 
 const MAX_COMMAND_LEN: usize = 0x100;
 
-pub fn exec() {
-    let mut buf: [u8; MAX_COMMAND_LEN] = [0; MAX_COMMAND_LEN];
-    loop {
-        print!("buddy# ");
-        gets(&mut buf);
-        if execute_command(&buf) {
-            break;
+pub struct Buddy;
+
+impl Command for Buddy {
+    fn name(&self) -> &'static str {
+        "buddy"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "interact with the dangerous buddy allocator"
+    }
+
+    fn exec(&self, _: &[String]) {
+        let mut buf: [u8; MAX_COMMAND_LEN] = [0; MAX_COMMAND_LEN];
+        loop {
+            print!("buddy# ");
+            gets(&mut buf);
+            if execute_command(&buf) {
+                break;
+            }
         }
     }
 }
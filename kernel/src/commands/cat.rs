@@ -1,17 +1,37 @@
+use super::Command;
 use crate::INITRAMFS_ADDR;
+use alloc::string::String;
 use filesystem::cpio::CpioArchive;
 use stdio::print;
 use stdio::println;
 
-pub fn exec(command: &[u8]) {
-    let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
-    let filename = &command[4..];
-    if let Some(data) = rootfs.get_file(core::str::from_utf8(filename).unwrap()) {
-        print!("{}", core::str::from_utf8(data).unwrap());
-    } else {
-        println!(
-            "File not found: {}",
-            core::str::from_utf8(filename).unwrap()
-        );
+pub struct Cat;
+
+impl Command for Cat {
+    fn name(&self) -> &'static str {
+        "cat"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<file>..."
+    }
+
+    fn description(&self) -> &'static str {
+        "print the content of a file in the initramfs"
+    }
+
+    fn exec(&self, args: &[String]) {
+        let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
+        if args.len() < 2 {
+            println!("Usage: {} {}", self.name(), self.usage());
+            return;
+        }
+        for filename in args.iter().skip(1) {
+            if let Some(data) = rootfs.get_file(filename) {
+                print!("{}", core::str::from_utf8(data).unwrap());
+            } else {
+                println!("File not found: {}", filename);
+            }
+        }
     }
 }
@@ -0,0 +1,27 @@
+use super::Command;
+use crate::param;
+use alloc::string::String;
+use stdio::println;
+
+pub struct Cmdline;
+
+impl Command for Cmdline {
+    fn name(&self) -> &'static str {
+        "cmdline"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "show the kernel command line and parameters"
+    }
+
+    fn exec(&self, _: &[String]) {
+        println!("{}", param::get().cmdline);
+        param::for_each(|name, value, description| {
+            println!("  {}={} ({})", name, value, description);
+        });
+    }
+}
@@ -0,0 +1,29 @@
+use super::Command;
+use crate::device;
+use alloc::string::String;
+use stdio::println;
+
+pub struct Devices;
+
+impl Command for Devices {
+    fn name(&self) -> &'static str {
+        "devices"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "list the devices bound to a driver"
+    }
+
+    fn exec(&self, _: &[String]) {
+        for binding in device::bindings() {
+            println!("{:<20} {}", binding.driver, binding.path);
+            for (addr, size) in binding.regions.iter() {
+                println!("{:<20}   {:#010x} - {:#010x}", "", addr, addr + size);
+            }
+        }
+    }
+}
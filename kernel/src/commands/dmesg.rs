@@ -0,0 +1,34 @@
+use super::Command;
+use alloc::string::String;
+use alloc::vec;
+use stdio::log;
+
+pub struct Dmesg;
+
+impl Command for Dmesg {
+    fn name(&self) -> &'static str {
+        "dmesg"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-c]"
+    }
+
+    fn description(&self) -> &'static str {
+        "print the kernel log, -c clears it afterwards"
+    }
+
+    fn exec(&self, args: &[String]) {
+        let mut buf = vec![0; log::LOG_BUF_SIZE];
+        let len = log::read(&mut buf);
+        for &c in buf[..len].iter() {
+            if c == b'\n' {
+                stdio::send(b'\r');
+            }
+            stdio::send(c);
+        }
+        if args.get(1).is_some_and(|arg| arg == "-c") {
+            log::clear();
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use super::Command;
+use crate::dtb::{self, Node, Property};
+use alloc::string::String;
+use stdio::{print, println};
+
+pub struct Dt;
+
+fn print_value(prop: &Property) {
+    if prop.value.is_empty() {
+        println!("{};", prop.name);
+    } else if prop.is_string_list() {
+        print!("{} =", prop.name);
+        for (i, s) in prop.as_strings().enumerate() {
+            print!("{} \"{}\"", if i == 0 { "" } else { "," }, s);
+        }
+        println!(";");
+    } else if prop.value.len() % 4 == 0 {
+        print!("{} = <", prop.name);
+        for (i, cell) in prop.as_u32s().iter().enumerate() {
+            print!("{}0x{:x}", if i == 0 { "" } else { " " }, cell);
+        }
+        println!(">;");
+    } else {
+        print!("{} = [", prop.name);
+        for (i, byte) in prop.value.iter().enumerate() {
+            print!("{}{:02x}", if i == 0 { "" } else { " " }, byte);
+        }
+        println!("];");
+    }
+}
+
+fn print_node(node: Node, depth: usize, recursive: bool) {
+    let indent = depth * 4;
+    let name = if node.name().is_empty() {
+        "/"
+    } else {
+        node.name()
+    };
+    println!("{:indent$}{} {{", "", name);
+    for prop in node.properties() {
+        print!("{:indent$}    ", "");
+        print_value(prop);
+    }
+    for child in node.children() {
+        if recursive {
+            print_node(child, depth + 1, true);
+        } else {
+            println!("{:indent$}    {} {{ ... }};", "", child.name());
+        }
+    }
+    println!("{:indent$}}};", "");
+}
+
+impl Command for Dt {
+    fn name(&self) -> &'static str {
+        "dt"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[-r] [path]"
+    }
+
+    fn description(&self) -> &'static str {
+        "show a device tree node, -r includes every descendant"
+    }
+
+    fn exec(&self, args: &[String]) {
+        let recursive = args.iter().any(|arg| arg == "-r");
+        let path = args[1..]
+            .iter()
+            .find(|arg| *arg != "-r")
+            .map_or("/", |arg| arg.as_str());
+        match dtb::get().find(path) {
+            Some(node) => print_node(node, 0, recursive),
+            None => println!("No such node: {}", path),
+        }
+    }
+}
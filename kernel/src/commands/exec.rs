@@ -1,29 +1,43 @@
+use super::Command;
 use crate::scheduler;
 use crate::INITRAMFS_ADDR;
 use alloc::string::String;
-use alloc::vec::Vec;
 use filesystem::cpio::CpioArchive;
 use stdio::println;
 
-pub fn exec(args: Vec<String>) -> ! {
-    println!("Executing exec command with args: {:?}", args);
-    let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
-    for filename in args.iter().skip(1) {
-        let filename = filename.as_bytes();
-        if let Some(data) = rootfs.get_file(core::str::from_utf8(filename).unwrap()) {
-            let program = scheduler::alloc_prog(data);
-            scheduler::get().create_thread(program.0, program.1);
-        } else {
-            println!(
-                "File not found: {}",
-                core::str::from_utf8(filename).unwrap()
-            );
-        }
+pub struct Exec;
+
+impl Command for Exec {
+    fn name(&self) -> &'static str {
+        "exec"
     }
 
-    if scheduler::get().ready_queue.is_empty() {
-        panic!("No threads to run!");
-    } else {
-        scheduler::get().run_threads();
+    fn usage(&self) -> &'static str {
+        "<program>..."
+    }
+
+    fn description(&self) -> &'static str {
+        "execute programs in the initramfs, never returns"
+    }
+
+    fn exec(&self, args: &[String]) {
+        println!("Executing exec command with args: {:?}", args);
+        let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
+        for filename in args.iter().skip(1) {
+            if let Some(data) = rootfs.get_file(filename) {
+                let args = [filename.clone()];
+                if let Err(errno) = scheduler::get().create_thread(data, &args) {
+                    println!("Cannot run {}: {}", filename, errno);
+                }
+            } else {
+                println!("File not found: {}", filename);
+            }
+        }
+
+        if scheduler::get().ready_queue.is_empty() {
+            println!("No threads to run!");
+        } else {
+            scheduler::get().run_threads();
+        }
     }
 }
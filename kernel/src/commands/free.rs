@@ -0,0 +1,45 @@
+use super::Command;
+use crate::allocator::meminfo::{self, bytes};
+use alloc::string::String;
+use stdio::println;
+
+pub struct Free;
+
+impl Command for Free {
+    fn name(&self) -> &'static str {
+        "free"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "show the amount of free and used memory in KiB"
+    }
+
+    fn exec(&self, _: &[String]) {
+        let info = meminfo::snapshot();
+        println!(
+            "{:6} {:>10} {:>10} {:>10} {:>10}",
+            "", "total", "used", "free", "reserved"
+        );
+        println!(
+            "{:6} {:>10} {:>10} {:>10} {:>10}",
+            "Mem:",
+            bytes(info.total) / 1024,
+            bytes(info.used()) / 1024,
+            bytes(info.free) / 1024,
+            bytes(info.reserved) / 1024
+        );
+        let slab = info.slab_bytes();
+        let slab_used = info.slab_used_bytes();
+        println!(
+            "{:6} {:>10} {:>10} {:>10}",
+            "Slab:",
+            slab / 1024,
+            slab_used / 1024,
+            (slab - slab_used) / 1024
+        );
+    }
+}
@@ -1,5 +1,23 @@
+use super::Command;
+use alloc::string::String;
 use stdio::println;
 
-pub fn exec() {
-    println!("Hello, world!");
+pub struct Hello;
+
+impl Command for Hello {
+    fn name(&self) -> &'static str {
+        "hello"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "print Hello, world!"
+    }
+
+    fn exec(&self, _: &[String]) {
+        println!("Hello, world!");
+    }
 }
@@ -1,24 +1,53 @@
+use super::Command;
+use alloc::format;
+use alloc::string::String;
 use stdio::println;
-pub fn exec() {
-    let width = 12;
-    println!("{:width$}: {}", "help", "print this help menu");
-    println!("{:width$}: {}", "hello", "print Hello, world!");
-    println!("{:width$}: {}", "reboot", "reboot the Raspberry Pi");
-    println!("{:width$}: {}", "ls", "list files in the initramfs");
-    println!(
-        "{:width$}: {}",
-        "cat", "print the content of a file in the initramfs"
-    );
-    println!(
-        "{:width$}: {}",
-        "exec", "execute a program in the initramfs"
-    );
-    println!(
-        "{:width$}: {}",
-        "setTimeOut", "print a message after some time"
-    );
-    println!(
-        "{:width$}: {}",
-        "buddy", "interact with the dangerous buddy allocator"
-    );
+
+pub struct Help;
+
+fn synopsis(command: &dyn Command) -> String {
+    if command.usage().is_empty() {
+        String::from(command.name())
+    } else {
+        format!("{} {}", command.name(), command.usage())
+    }
+}
+
+impl Command for Help {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[command]"
+    }
+
+    fn description(&self) -> &'static str {
+        "print this help menu, or the usage of a command"
+    }
+
+    fn exec(&self, args: &[String]) {
+        if let Some(name) = args.get(1) {
+            match super::find(name) {
+                Some(command) => {
+                    println!("Usage: {}", synopsis(command));
+                    println!("{}", command.description());
+                }
+                None => println!("Unknown command: {}", name),
+            }
+            return;
+        }
+        let width = super::commands()
+            .iter()
+            .map(|command| synopsis(*command).len())
+            .max()
+            .unwrap_or(0);
+        for command in super::commands() {
+            println!("{:width$}: {}", synopsis(*command), command.description());
+        }
+        println!(
+            "{:width$}: {}",
+            "a | b", "run programs in the initramfs connected by pipes"
+        );
+    }
 }
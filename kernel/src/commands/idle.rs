@@ -0,0 +1,44 @@
+use super::Command;
+use crate::timer;
+use alloc::string::String;
+use stdio::println;
+
+pub struct Idle;
+
+impl Command for Idle {
+    fn name(&self) -> &'static str {
+        "idle"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "show idle residency statistics"
+    }
+
+    fn exec(&self, _: &[String]) {
+        let tm = timer::manager::get();
+        let stats = timer::idle::get();
+        let freq = tm.get_frequency();
+        let now = tm.get_current();
+        println!("Uptime: {} ms", now / (freq / 1000));
+        println!("Idle: {} ms", stats.idle_ticks / (freq / 1000));
+        println!(
+            "Idle residency: {}.{:02}%",
+            stats.idle_ticks * 100 / now,
+            stats.idle_ticks * 10000 / now % 100
+        );
+        println!("Wakeups: {}", stats.wakeups);
+        println!("Scheduler ticks: {}", stats.ticks_fired);
+        println!("Tick stopped: {} times", stats.ticks_stopped);
+        match tm.next_expiry() {
+            Some(expiry) => println!(
+                "Next deadline: in {} ms",
+                expiry.saturating_sub(now) / (freq / 1000)
+            ),
+            None => println!("Next deadline: none"),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use super::Command;
+use alloc::string::String;
+use stdio::println;
+
+pub struct Leaks;
+
+impl Command for Leaks {
+    fn name(&self) -> &'static str {
+        "leaks"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "list outstanding heap allocations grouped by caller"
+    }
+
+    #[cfg(not(feature = "heap-debug"))]
+    fn exec(&self, _: &[String]) {
+        println!("The kernel was built without the heap-debug feature");
+    }
+
+    #[cfg(feature = "heap-debug")]
+    fn exec(&self, _: &[String]) {
+        use crate::allocator::debug::{self, Record};
+        use alloc::vec;
+        use alloc::vec::Vec;
+
+        // Allocating the copy adds records of its own, leave room for them
+        let empty = Record {
+            ptr: 0,
+            size: 0,
+            callers: [0; debug::CALLER_DEPTH],
+        };
+        let mut records = vec![empty; debug::outstanding() + 16];
+        let (len, untracked) = debug::snapshot(&mut records);
+        records.truncate(len);
+        records.sort_unstable_by_key(|record| record.callers);
+
+        // (callers, count, bytes) per call chain, most bytes first
+        let mut groups: Vec<([usize; debug::CALLER_DEPTH], usize, usize)> = Vec::new();
+        for record in records.iter() {
+            match groups.last_mut() {
+                Some(group) if group.0 == record.callers => {
+                    group.1 += 1;
+                    group.2 += record.size;
+                }
+                _ => groups.push((record.callers, 1, record.size)),
+            }
+        }
+        groups.sort_unstable_by_key(|group| core::cmp::Reverse(group.2));
+
+        println!("{:>6} {:>10}  callers", "count", "bytes");
+        for (callers, count, bytes) in groups.iter() {
+            println!("{:>6} {:>10}  {:x?}", count, bytes, callers);
+        }
+        println!("{} allocations outstanding, {} untracked", len, untracked);
+    }
+}
@@ -0,0 +1,59 @@
+use super::Command;
+use alloc::string::String;
+use stdio::log::{self, Level};
+use stdio::println;
+
+pub struct LogLevel;
+
+fn parse_level(s: &str) -> Option<Level> {
+    let level = Level::parse(s);
+    if level.is_none() {
+        println!("Invalid level: {}", s);
+    }
+    level
+}
+
+impl Command for LogLevel {
+    fn name(&self) -> &'static str {
+        "loglevel"
+    }
+
+    fn usage(&self) -> &'static str {
+        "[level | default <level> | <module> <level|reset>]"
+    }
+
+    fn description(&self) -> &'static str {
+        "show or set the console level and the per-module log filters"
+    }
+
+    fn exec(&self, args: &[String]) {
+        match args {
+            [_] => {
+                println!("console: {}", log::console_level());
+                println!("default: {}", log::default_level());
+                log::for_each_filter(|module, level| println!("{}: {}", module, level));
+            }
+            [_, level] => {
+                if let Some(level) = parse_level(level) {
+                    log::set_console_level(level);
+                }
+            }
+            [_, module, level] if module == "default" => {
+                if let Some(level) = parse_level(level) {
+                    log::set_default_level(level);
+                }
+            }
+            [_, module, level] if level == "reset" => {
+                log::set_filter(module, None);
+            }
+            [_, module, level] => {
+                if let Some(level) = parse_level(level) {
+                    if !log::set_filter(module, Some(level)) {
+                        println!("Too many filters or module name too long");
+                    }
+                }
+            }
+            _ => println!("Usage: {} {}", self.name(), self.usage()),
+        }
+    }
+}
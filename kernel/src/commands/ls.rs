@@ -1,7 +1,25 @@
+use super::Command;
 use crate::INITRAMFS_ADDR;
+use alloc::string::String;
 use filesystem::cpio::CpioArchive;
 
-pub fn exec() {
-    let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
-    rootfs.print_file_list();
+pub struct Ls;
+
+impl Command for Ls {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "list files in the initramfs"
+    }
+
+    fn exec(&self, _: &[String]) {
+        let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
+        rootfs.print_file_list();
+    }
 }
@@ -0,0 +1,73 @@
+use super::Command;
+use crate::allocator::buddy::LAYER_COUNT;
+use crate::allocator::meminfo::{self, bytes};
+use crate::scheduler;
+use alloc::string::String;
+use stdio::println;
+
+pub struct Meminfo;
+
+impl Command for Meminfo {
+    fn name(&self) -> &'static str {
+        "meminfo"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "show detailed memory statistics"
+    }
+
+    fn exec(&self, _: &[String]) {
+        let info = meminfo::snapshot();
+        let kib = |bytes: usize| bytes / 1024;
+        println!("MemTotal:     {:>10} KiB", kib(bytes(info.total)));
+        println!("MemFree:      {:>10} KiB", kib(bytes(info.free)));
+        println!("MemUsed:      {:>10} KiB", kib(bytes(info.used())));
+        println!("MemReserved:  {:>10} KiB", kib(bytes(info.reserved)));
+        println!("Slab:         {:>10} KiB", kib(info.slab_bytes()));
+        println!("SlabUsed:     {:>10} KiB", kib(info.slab_used_bytes()));
+        println!("HeapLarge:    {:>10} KiB", kib(info.large_bytes));
+        println!("PageTables:   {:>10} KiB", kib(bytes(info.page_tables)));
+
+        // The unusable index of a layer is the share of free memory in
+        // blocks too small for it
+        println!();
+        println!("{:>5} {:>10} {:>10}", "layer", "free", "unusable");
+        for layer in 0..LAYER_COUNT {
+            let index = info.unusable_index(layer);
+            println!(
+                "{:>5} {:>10} {:>6}.{:03}",
+                layer,
+                info.free_blocks[layer],
+                index / 1000,
+                index % 1000
+            );
+        }
+
+        println!();
+        println!("{:>6} {:>8} {:>8} {:>10}", "size", "slabs", "in use", "KiB");
+        for cache in info.caches.iter() {
+            println!(
+                "{:>6} {:>8} {:>8} {:>10}",
+                cache.size,
+                cache.slabs,
+                cache.in_use,
+                kib(cache.slabs * cache.slab_size)
+            );
+        }
+
+        println!();
+        println!("{:>5} {:<16} {:>10}", "PID", "NAME", "RSS KiB");
+        for proc in scheduler::get().procinfo() {
+            println!(
+                "{:>5} {:<16} {:>10}",
+                proc.pid,
+                proc.name(),
+                proc.mem / 1024
+            );
+        }
+    }
+}
@@ -1,50 +1,98 @@
+mod args;
 mod buddy;
 mod cat;
+mod cmdline;
+mod devices;
+mod dmesg;
+mod dt;
 mod exec;
+mod free;
 mod hello;
 mod help;
+mod idle;
+mod leaks;
+mod loglevel;
 mod ls;
+mod meminfo;
+mod pipeline;
 mod reboot;
 mod set_time_out;
-use stdio::println;
+mod slabinfo;
+use stdio::{debug, println};
 
 use alloc::string::String;
-use alloc::string::ToString;
-use alloc::vec::Vec;
+
+pub trait Command: Sync {
+    fn name(&self) -> &'static str;
+    // Arguments following the name, e.g. "<file>..."
+    fn usage(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    // args[0] is the command name
+    fn exec(&self, args: &[String]);
+}
+
+static COMMANDS: [&dyn Command; 18] = [
+    &buddy::Buddy,
+    &cat::Cat,
+    &cmdline::Cmdline,
+    &devices::Devices,
+    &dmesg::Dmesg,
+    &dt::Dt,
+    &exec::Exec,
+    &free::Free,
+    &hello::Hello,
+    &help::Help,
+    &idle::Idle,
+    &leaks::Leaks,
+    &loglevel::LogLevel,
+    &ls::Ls,
+    &meminfo::Meminfo,
+    &reboot::Reboot,
+    &set_time_out::SetTimeOut,
+    &slabinfo::Slabinfo,
+];
+
+pub fn commands() -> &'static [&'static dyn Command] {
+    &COMMANDS
+}
+
+pub fn find(name: &str) -> Option<&'static dyn Command> {
+    COMMANDS
+        .iter()
+        .copied()
+        .find(|command| command.name() == name)
+}
 
 pub fn execute(command: &[u8]) {
     let command = match command.iter().position(|&c| c == 0) {
         Some(i) => &command[..i],
         None => command,
     };
-    let args: Vec<String> = core::str::from_utf8(command)
-        .unwrap()
-        .split_whitespace()
-        .map(|s| s.to_string())
-        .collect();
-    println!("Executing command: {:?}", args);
+    let command = match core::str::from_utf8(command) {
+        Ok(command) => command,
+        Err(_) => {
+            println!("Invalid command");
+            return;
+        }
+    };
+    let mut stages = match args::parse(command) {
+        Ok(stages) => stages,
+        Err(err) => {
+            println!("Parse error: {}", err);
+            return;
+        }
+    };
+    debug!("Executing command: {:?}", stages);
+    if stages.len() > 1 {
+        pipeline::exec(stages);
+        return;
+    }
+    let args = stages.pop().unwrap();
     if args.is_empty() {
         return;
-    } else if args[0] == "hello" {
-        hello::exec();
-    } else if args[0] == "help" {
-        help::exec();
-    } else if args[0] == "reboot" {
-        reboot::exec();
-    } else if args[0] == "ls" {
-        ls::exec();
-    } else if args[0] == "cat" {
-        cat::exec(&command);
-    } else if args[0] == "exec" {
-        exec::exec(args);
-    } else if args[0] == "setTimeOut" {
-        set_time_out::exec(&command);
-    } else if args[0] == "buddy" {
-        buddy::exec();
-    } else {
-        println!(
-            "Unknown command: {}",
-            core::str::from_utf8(command).unwrap()
-        );
+    }
+    match find(&args[0]) {
+        Some(command) => command.exec(&args),
+        None => println!("Unknown command: {}", args[0]),
     }
 }
@@ -0,0 +1,51 @@
+use crate::file::{pipe, File, STDIN, STDOUT};
+use crate::scheduler;
+use crate::INITRAMFS_ADDR;
+use alloc::string::String;
+use alloc::vec::Vec;
+use filesystem::cpio::CpioArchive;
+use stdio::println;
+
+// Run `a | b | ...`, connecting the stdout of each program to the stdin of the next
+pub fn exec(stages: Vec<Vec<String>>) {
+    if stages.iter().any(|argv| argv.is_empty()) {
+        println!("Invalid pipeline: empty command");
+        return;
+    }
+    let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
+    let mut programs = Vec::new();
+    for argv in stages.iter() {
+        match rootfs.get_file(&argv[0]) {
+            Some(data) => programs.push(data),
+            None => {
+                println!("File not found: {}", argv[0]);
+                return;
+            }
+        }
+    }
+
+    let sched = scheduler::get();
+    let mut stdin = None;
+    for (i, (data, args)) in programs.iter().zip(stages.iter()).enumerate() {
+        // The stages started so far still run, the last one sees EPIPE
+        let tid = match sched.create_thread(data, args) {
+            Ok(tid) => tid,
+            Err(errno) => {
+                println!("Cannot run {}: {}", args[0], errno);
+                break;
+            }
+        };
+        let files = &mut sched.threads[tid].as_mut().unwrap().files;
+        if let Some(reader) = stdin.take() {
+            files.set(STDIN, File::PipeRead(reader));
+        }
+        if i + 1 < programs.len() {
+            let (reader, writer) = pipe::new();
+            files.set(STDOUT, File::PipeWrite(writer));
+            stdin = Some(reader);
+        }
+    }
+    if !sched.ready_queue.is_empty() {
+        sched.run_threads();
+    }
+}
@@ -1,5 +1,23 @@
+use super::Command;
+use alloc::string::String;
 use driver::watchdog;
 
-pub fn exec() {
-    watchdog::reset(100);
+pub struct Reboot;
+
+impl Command for Reboot {
+    fn name(&self) -> &'static str {
+        "reboot"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "reboot the Raspberry Pi"
+    }
+
+    fn exec(&self, _: &[String]) {
+        watchdog::reset(100);
+    }
 }
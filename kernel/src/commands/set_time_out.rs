@@ -1,41 +1,40 @@
+use super::Command;
 use crate::timer;
 use alloc::boxed::Box;
 use alloc::string::String;
-use alloc::vec::Vec;
 use core::time::Duration;
-use stdio::debug;
 use stdio::println;
 
-pub fn exec(command: &[u8]) {
-    let args = match core::str::from_utf8(&command[10..]) {
-        Ok(s) => s.split_whitespace().collect::<Vec<&str>>(),
-        Err(_) => {
-            debug!("Invalid arguments");
+pub struct SetTimeOut;
+
+impl Command for SetTimeOut {
+    fn name(&self) -> &'static str {
+        "setTimeOut"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<ms> <message>..."
+    }
+
+    fn description(&self) -> &'static str {
+        "print a message after some time"
+    }
+
+    fn exec(&self, args: &[String]) {
+        if args.len() < 3 {
+            println!("Usage: {} {}", self.name(), self.usage());
             return;
         }
-    };
-    if args.len() < 2 {
-        println!("Invalid arguments");
-        return;
-    }
-    // println!("Args: {:?}", args);
-    let delay = match args.get(0) {
-        Some(s) => match s.parse::<u64>() {
+        let delay = match args[1].parse::<u64>() {
             Ok(n) => n,
             Err(_) => {
                 println!("Invalid delay");
                 return;
             }
-        },
-        None => {
-            println!("Invalid delay");
-            return;
-        }
-    };
-    // debug!("Delay: {}", delay);
-    let message = args[1..].join(" ");
-    // debug!("Message: {}", message);
-    add_timer(Duration::from_millis(delay), message);
+        };
+        let message = args[2..].join(" ");
+        add_timer(Duration::from_millis(delay), message);
+    }
 }
 
 fn add_timer(duration: Duration, message: String) {
@@ -0,0 +1,40 @@
+use super::Command;
+use crate::allocator::slab;
+use alloc::string::String;
+use stdio::println;
+
+pub struct Slabinfo;
+
+impl Command for Slabinfo {
+    fn name(&self) -> &'static str {
+        "slabinfo"
+    }
+
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    fn description(&self) -> &'static str {
+        "show the kernel heap size classes"
+    }
+
+    fn exec(&self, _: &[String]) {
+        println!(
+            "{:>6} {:>6} {:>6} {:>8} {:>8} {:>10} {:>10}",
+            "size", "slab", "slabs", "objects", "in use", "allocs", "frees"
+        );
+        slab::for_each_cache(|cache| {
+            println!(
+                "{:>6} {:>6} {:>6} {:>8} {:>8} {:>10} {:>10}",
+                cache.size,
+                cache.slab_size,
+                cache.slabs,
+                cache.objects,
+                cache.in_use,
+                cache.allocs,
+                cache.frees
+            );
+        });
+        println!("Large allocations: {} bytes", slab::large_bytes());
+    }
+}
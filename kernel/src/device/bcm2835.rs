@@ -0,0 +1,103 @@
+// The BCM2835 family peripherals behind the driver crate. Probing points the
+// crate's register blocks at the addresses from the device tree.
+
+use super::{Device, Driver};
+use driver::mmio::regs::{Block, IrqReg, MmioReg};
+use driver::mmio::Mmio;
+
+fn set_base(device: &Device, block: Block) -> Result<(), &'static str> {
+    let base = device
+        .reg_base(0)
+        .ok_or("registers outside the device mapping")?;
+    driver::mmio::regs::set_base(block, base);
+    Ok(())
+}
+
+// The auxiliary peripherals, of which the kernel uses the mini UART
+pub struct Aux;
+
+impl Driver for Aux {
+    fn name(&self) -> &'static str {
+        "bcm2835-aux"
+    }
+
+    fn compatible(&self) -> &'static [&'static str] {
+        &["brcm,bcm2835-aux"]
+    }
+
+    fn probe(&self, device: &Device) -> Result<(), &'static str> {
+        set_base(device, Block::Aux)?;
+        // The ARM controller numbers GPU interrupts as <bank irq>, and the
+        // mini UART was enabled before the device tree was read
+        if let Some(&[1, irq]) = device.interrupts.first().map(|spec| spec.as_slice()) {
+            Mmio::write_reg(MmioReg::Irq(IrqReg::S1), 1 << irq);
+        }
+        Ok(())
+    }
+}
+
+pub struct Gpio;
+
+impl Driver for Gpio {
+    fn name(&self) -> &'static str {
+        "bcm2835-gpio"
+    }
+
+    fn compatible(&self) -> &'static [&'static str] {
+        &["brcm,bcm2835-gpio"]
+    }
+
+    fn probe(&self, device: &Device) -> Result<(), &'static str> {
+        set_base(device, Block::Gpio)
+    }
+}
+
+pub struct Mailbox;
+
+impl Driver for Mailbox {
+    fn name(&self) -> &'static str {
+        "bcm2835-mbox"
+    }
+
+    fn compatible(&self) -> &'static [&'static str] {
+        &["brcm,bcm2835-mbox"]
+    }
+
+    fn probe(&self, device: &Device) -> Result<(), &'static str> {
+        set_base(device, Block::Mailbox)
+    }
+}
+
+// The power management block, which holds the watchdog used for reboot
+pub struct Watchdog;
+
+impl Driver for Watchdog {
+    fn name(&self) -> &'static str {
+        "bcm2835-pm"
+    }
+
+    fn compatible(&self) -> &'static [&'static str] {
+        &["brcm,bcm2835-pm", "brcm,bcm2835-pm-wdt"]
+    }
+
+    fn probe(&self, device: &Device) -> Result<(), &'static str> {
+        set_base(device, Block::Pm)
+    }
+}
+
+// The ARM interrupt controller routing GPU peripheral interrupts
+pub struct IntController;
+
+impl Driver for IntController {
+    fn name(&self) -> &'static str {
+        "bcm2835-armctrl-ic"
+    }
+
+    fn compatible(&self) -> &'static [&'static str] {
+        &["brcm,bcm2836-armctrl-ic", "brcm,bcm2835-armctrl-ic"]
+    }
+
+    fn probe(&self, device: &Device) -> Result<(), &'static str> {
+        set_base(device, Block::Irq)
+    }
+}
@@ -0,0 +1,50 @@
+// The per-core interrupt controller of the BCM2836 and later, which routes the
+// ARM generic timer interrupts and reports each core's pending sources.
+
+use super::{Device, Driver};
+use crate::mmu::config::DEVICE_BASE;
+use core::ptr::{read_volatile, write_volatile};
+
+const CORE0_TIMER_IRQ_CTRL: u32 = 0x40;
+const CORE0_IRQ_SOURCE: u32 = 0x60;
+
+// Bit of the non-secure physical timer, both in the control and source registers
+pub const CNTPNSIRQ: u32 = 1 << 1;
+
+// The address on the Raspberry Pi 3, used until the device tree is probed
+static mut BASE: usize = DEVICE_BASE as usize + 0x4000_0000;
+
+pub struct LocalIntc;
+
+impl Driver for LocalIntc {
+    fn name(&self) -> &'static str {
+        "bcm2836-l1-intc"
+    }
+
+    fn compatible(&self) -> &'static [&'static str] {
+        &["brcm,bcm2836-l1-intc"]
+    }
+
+    fn probe(&self, device: &Device) -> Result<(), &'static str> {
+        let base = device
+            .reg_base(0)
+            .ok_or("registers outside the device mapping")?;
+        unsafe {
+            BASE = base;
+        }
+        Ok(())
+    }
+}
+
+fn reg(offset: u32) -> *mut u32 {
+    unsafe { (BASE + offset as usize) as *mut u32 }
+}
+
+// Route the given timer interrupts of core 0 to its IRQ line, 0 masks them all
+pub fn set_core0_timer_irqs(mask: u32) {
+    unsafe { write_volatile(reg(CORE0_TIMER_IRQ_CTRL), mask) }
+}
+
+pub fn core0_irq_source() -> u32 {
+    unsafe { read_volatile(reg(CORE0_IRQ_SOURCE)) }
+}
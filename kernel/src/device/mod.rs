@@ -0,0 +1,102 @@
+// Drivers declare the compatible strings they handle, and probe() walks the
+// device tree to bind each enabled node to the first driver that matches it,
+// passing the node's registers translated to CPU addresses and its interrupts.
+
+mod bcm2835;
+pub mod local_intc;
+
+use crate::dtb::{self, Node};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ptr::{addr_of, addr_of_mut};
+use stdio::{debug, info, warn};
+
+pub struct Device {
+    // (address, size) pairs as seen from the CPU
+    pub regions: Vec<(u64, u64)>,
+    // Specifiers in the format of the node's interrupt parent
+    pub interrupts: Vec<Vec<u32>>,
+}
+
+impl Device {
+    // Where the kernel reaches the index-th register region, None when it is
+    // not within the peripherals
+    pub fn reg_base(&self, index: usize) -> Option<usize> {
+        let (addr, _) = *self.regions.get(index)?;
+        crate::mmu::device_addr(addr).map(|addr| addr as usize)
+    }
+}
+
+pub trait Driver: Sync {
+    fn name(&self) -> &'static str;
+    fn compatible(&self) -> &'static [&'static str];
+    fn probe(&self, device: &Device) -> Result<(), &'static str>;
+}
+
+static DRIVERS: [&dyn Driver; 6] = [
+    &bcm2835::Aux,
+    &bcm2835::Gpio,
+    &bcm2835::Mailbox,
+    &bcm2835::Watchdog,
+    &bcm2835::IntController,
+    &local_intc::LocalIntc,
+];
+
+pub struct Binding {
+    pub driver: &'static str,
+    pub path: String,
+    pub regions: Vec<(u64, u64)>,
+}
+
+static mut BINDINGS: Vec<Binding> = Vec::new();
+
+// Devices bound so far, in probe order
+pub fn bindings() -> &'static [Binding] {
+    unsafe { &*addr_of!(BINDINGS) }
+}
+
+fn matching_driver(node: &Node) -> Option<&'static dyn Driver> {
+    // The compatible list goes from most to least specific
+    node.compatible().find_map(|compatible| {
+        DRIVERS
+            .iter()
+            .copied()
+            .find(|driver| driver.compatible().contains(&compatible))
+    })
+}
+
+pub fn probe() {
+    for node in dtb::get().nodes() {
+        if !node.is_enabled() {
+            continue;
+        }
+        let driver = match matching_driver(&node) {
+            Some(driver) => driver,
+            None => continue,
+        };
+        let device = Device {
+            regions: node.regions(),
+            interrupts: node.interrupts(),
+        };
+        let path = node.path();
+        match driver.probe(&device) {
+            Ok(()) => {
+                debug!(
+                    "{}: bound to {} at {:x?}",
+                    driver.name(),
+                    path,
+                    device.regions
+                );
+                unsafe {
+                    (*addr_of_mut!(BINDINGS)).push(Binding {
+                        driver: driver.name(),
+                        path,
+                        regions: device.regions,
+                    });
+                }
+            }
+            Err(err) => warn!("{}: failed to probe {}: {}", driver.name(), path, err),
+        }
+    }
+    info!("Probed {} devices", bindings().len());
+}
@@ -1,80 +1,306 @@
+use super::mem_rsvmap::MemRsv;
 use super::strings::StringMap;
-use super::utils::read_string;
+use super::utils::{read_be_u32, read_string};
 use alloc::string::String;
 use alloc::vec::Vec;
 
+// Cell counts assumed when a node does not specify them
+const DEFAULT_ADDRESS_CELLS: u32 = 2;
+const DEFAULT_SIZE_CELLS: u32 = 1;
+
+// A device tree unflattened into an arena of nodes, nodes[0] being the root
+#[derive(Debug)]
+pub struct DeviceTree {
+    nodes: Vec<NodeData>,
+    pub mem_rsvmap: Vec<MemRsv>,
+}
+
 #[derive(Debug)]
-pub struct Dt {
+struct NodeData {
     name: String,
+    parent: Option<usize>,
+    children: Vec<usize>,
     properties: Vec<Property>,
-    children: Vec<Dt>,
-    length: u32,
 }
 
-impl Dt {
-    pub fn load(dt_addr: u32, strings: &StringMap) -> Dt {
-        let mut dt = Dt {
-            name: String::new(),
-            properties: Vec::new(),
-            children: Vec::new(),
-            length: 0,
-        };
-
+impl DeviceTree {
+    pub fn load(dt_addr: u64, strings: &StringMap, mem_rsvmap: Vec<MemRsv>) -> DeviceTree {
+        let mut nodes: Vec<NodeData> = Vec::new();
+        let mut stack: Vec<usize> = Vec::new();
         let mut addr = dt_addr;
-        let lexical = unsafe { core::ptr::read_volatile(addr as *const u32) };
-
-        assert_eq!(lexical.swap_bytes(), Lexical::BeginNode as u32);
-
-        addr += 4;
-        dt.name = read_string(addr);
-        addr += dt.name.len() as u32 + 1;
-        addr = (addr + 3) & !3;
-
         loop {
-            let lexical = unsafe { core::ptr::read_volatile(addr as *const u32) };
-            let lexical = lexical.swap_bytes();
-            let lexical = Lexical::from_u32(lexical);
+            let lexical = Lexical::from_u32(read_be_u32(addr));
+            addr += 4;
             match lexical {
                 Lexical::BeginNode => {
-                    let child = Dt::load(addr, strings);
-                    addr += child.length;
-                    dt.children.push(child);
+                    let name = read_string(addr);
+                    addr = (addr + name.len() as u64 + 1 + 3) & !3;
+                    let idx = nodes.len();
+                    let parent = stack.last().copied();
+                    if let Some(parent) = parent {
+                        nodes[parent].children.push(idx);
+                    }
+                    nodes.push(NodeData {
+                        name,
+                        parent,
+                        children: Vec::new(),
+                        properties: Vec::new(),
+                    });
+                    stack.push(idx);
                 }
                 Lexical::EndNode => {
-                    addr += 4;
-                    break;
+                    stack.pop();
                 }
                 Lexical::Prop => {
-                    addr += 4;
-                    let properties = Property::load(addr, strings);
-                    addr += properties.length + 8;
-                    dt.properties.push(properties);
-                    addr = (addr + 3) & !3;
-                }
-                Lexical::Nop => {
-                    addr += 4;
-                    continue;
-                }
-                Lexical::End => {
-                    break;
+                    let property = Property::load(addr, strings);
+                    addr = (addr + 8 + property.value.len() as u64 + 3) & !3;
+                    let node = *stack.last().expect("Property outside of a node");
+                    nodes[node].properties.push(property);
                 }
+                Lexical::Nop => {}
+                Lexical::End => break,
             }
         }
-        dt.length = addr - dt_addr;
-        dt
+        assert!(!nodes.is_empty(), "Empty device tree");
+        DeviceTree { nodes, mem_rsvmap }
     }
-    pub fn get(&self, name: &str) -> Option<&Property> {
-        if let Some(prop) = self.properties.iter().find(|prop| prop.name == name) {
-            return Some(prop);
-        } else {
-            for child in self.children.iter() {
-                if let Some(prop) = child.get(name) {
-                    return Some(prop);
-                }
+
+    fn node(&self, idx: usize) -> Node<'_> {
+        Node { tree: self, idx }
+    }
+
+    pub fn root(&self) -> Node<'_> {
+        self.node(0)
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = Node<'_>> {
+        (0..self.nodes.len()).map(move |idx| self.node(idx))
+    }
+
+    // Look up an absolute path like /soc/serial@7e215040, or an alias like
+    // serial0. The unit address may be left out if it is unambiguous.
+    pub fn find(&self, path: &str) -> Option<Node<'_>> {
+        if !path.starts_with('/') {
+            let (alias, rest) = path.split_once('/').unwrap_or((path, ""));
+            let target = self.find("/aliases")?.property(alias)?.as_str()?;
+            let node = self.find(target)?;
+            return if rest.is_empty() {
+                Some(node)
+            } else {
+                node.find(rest)
+            };
+        }
+        self.root().find(path)
+    }
+
+    pub fn find_by_phandle(&self, phandle: u32) -> Option<Node<'_>> {
+        self.nodes().find(|node| node.phandle() == Some(phandle))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    tree: &'a DeviceTree,
+    idx: usize,
+}
+
+impl<'a> Node<'a> {
+    fn data(&self) -> &'a NodeData {
+        &self.tree.nodes[self.idx]
+    }
+
+    // The full name including the unit address, empty for the root
+    pub fn name(&self) -> &'a str {
+        &self.data().name
+    }
+
+    // The name without the unit address
+    pub fn base_name(&self) -> &'a str {
+        self.name().split('@').next().unwrap_or("")
+    }
+
+    #[allow(dead_code)]
+    pub fn unit_address(&self) -> Option<&'a str> {
+        self.name().split_once('@').map(|(_, unit)| unit)
+    }
+
+    pub fn path(&self) -> String {
+        let mut components = Vec::new();
+        let mut node = Some(*self);
+        while let Some(cur) = node {
+            if cur.idx != 0 {
+                components.push(cur.name());
+            }
+            node = cur.parent();
+        }
+        let mut path = String::new();
+        for component in components.iter().rev() {
+            path.push('/');
+            path.push_str(component);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+
+    pub fn parent(&self) -> Option<Node<'a>> {
+        self.data().parent.map(|idx| self.tree.node(idx))
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = Node<'a>> {
+        let tree = self.tree;
+        self.data().children.iter().map(move |&idx| tree.node(idx))
+    }
+
+    pub fn child(&self, name: &str) -> Option<Node<'a>> {
+        let exact = self.children().find(|child| child.name() == name);
+        if exact.is_some() || name.contains('@') {
+            return exact;
+        }
+        let mut matches = self.children().filter(|child| child.base_name() == name);
+        match (matches.next(), matches.next()) {
+            (Some(child), None) => Some(child),
+            _ => None,
+        }
+    }
+
+    // Path relative to this node
+    pub fn find(&self, path: &str) -> Option<Node<'a>> {
+        path.split('/')
+            .filter(|component| !component.is_empty())
+            .try_fold(*self, |node, component| node.child(component))
+    }
+
+    pub fn properties(&self) -> impl Iterator<Item = &'a Property> {
+        self.data().properties.iter()
+    }
+
+    pub fn property(&self, name: &str) -> Option<&'a Property> {
+        self.properties().find(|prop| prop.name == name)
+    }
+
+    pub fn phandle(&self) -> Option<u32> {
+        self.property("phandle")
+            .or_else(|| self.property("linux,phandle"))
+            .and_then(|prop| prop.as_u32())
+    }
+
+    pub fn compatible(&self) -> impl Iterator<Item = &'a str> {
+        self.property("compatible")
+            .map(|prop| prop.as_strings())
+            .into_iter()
+            .flatten()
+    }
+
+    // A missing status means the device is usable
+    pub fn is_enabled(&self) -> bool {
+        match self.property("status").and_then(|prop| prop.as_str()) {
+            Some(status) => status == "okay" || status == "ok",
+            None => true,
+        }
+    }
+
+    // #address-cells and #size-cells that this node imposes on its children
+    pub fn address_cells(&self) -> u32 {
+        self.property("#address-cells")
+            .and_then(|prop| prop.as_u32())
+            .unwrap_or(DEFAULT_ADDRESS_CELLS)
+    }
+
+    pub fn size_cells(&self) -> u32 {
+        self.property("#size-cells")
+            .and_then(|prop| prop.as_u32())
+            .unwrap_or(DEFAULT_SIZE_CELLS)
+    }
+
+    // The (address, size) pairs of reg, in the address space of the parent bus
+    pub fn reg(&self) -> Vec<(u64, u64)> {
+        let parent = match self.parent() {
+            Some(parent) => parent,
+            None => return Vec::new(),
+        };
+        match self.property("reg") {
+            Some(prop) => prop.as_pairs(parent.address_cells(), parent.size_cells()),
+            None => Vec::new(),
+        }
+    }
+
+    // The reg entries translated into CPU physical addresses
+    pub fn regions(&self) -> Vec<(u64, u64)> {
+        self.reg()
+            .into_iter()
+            .filter_map(|(addr, size)| Some((self.translate(addr)?, size)))
+            .collect()
+    }
+
+    // Translate an address on the parent bus of this node to a CPU physical
+    // address through the ranges of every bus up to the root
+    pub fn translate(&self, mut addr: u64) -> Option<u64> {
+        let mut bus = self.parent()?;
+        while let Some(parent) = bus.parent() {
+            let ranges = bus.property("ranges")?;
+            // An empty ranges is an identity mapping
+            if !ranges.value.is_empty() {
+                let child_cells = bus.address_cells() as usize;
+                let parent_cells = parent.address_cells() as usize;
+                let size_cells = bus.size_cells() as usize;
+                let entry = child_cells + parent_cells + size_cells;
+                let cells = ranges.as_u32s();
+                addr = cells.chunks_exact(entry).find_map(|range| {
+                    let child = cells_to_u64(&range[..child_cells]);
+                    let parent = cells_to_u64(&range[child_cells..child_cells + parent_cells]);
+                    let size = cells_to_u64(&range[child_cells + parent_cells..]);
+                    (child <= addr && addr - child < size).then(|| addr - child + parent)
+                })?;
+            }
+            bus = parent;
+        }
+        Some(addr)
+    }
+
+    // The node handling the interrupts of this node, from the closest
+    // interrupt-parent up the tree
+    pub fn interrupt_parent(&self) -> Option<Node<'a>> {
+        let mut node = Some(*self);
+        while let Some(cur) = node {
+            if let Some(phandle) = cur.property("interrupt-parent").and_then(|p| p.as_u32()) {
+                return self.tree.find_by_phandle(phandle);
             }
+            node = cur.parent();
         }
         None
     }
+
+    // The interrupt specifiers, each #interrupt-cells of the interrupt parent long
+    pub fn interrupts(&self) -> Vec<Vec<u32>> {
+        let cells = match self.property("interrupts") {
+            Some(prop) => prop.as_u32s(),
+            None => return Vec::new(),
+        };
+        let count = self
+            .interrupt_parent()
+            .and_then(|parent| parent.property("#interrupt-cells"))
+            .and_then(|prop| prop.as_u32())
+            .unwrap_or(1)
+            .max(1) as usize;
+        cells
+            .chunks_exact(count)
+            .map(|chunk| chunk.to_vec())
+            .collect()
+    }
+}
+
+impl core::fmt::Debug for Node<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Node({})", self.path())
+    }
+}
+
+fn cells_to_u64(cells: &[u32]) -> u64 {
+    cells
+        .iter()
+        .fold(0u64, |acc, &cell| acc.wrapping_shl(32) | cell as u64)
 }
 
 #[derive(Debug)]
@@ -99,54 +325,92 @@ impl Lexical {
     }
 }
 
+// The raw big endian bytes of a property, interpreted by the accessors
 #[derive(Debug)]
-#[allow(dead_code)]
-pub enum PropValue {
-    Integer(u32),
-    String(String),
+pub struct Property {
+    pub name: String,
+    pub value: Vec<u8>,
 }
 
-struct PropertyHeader {
-    length: u32,
-    nameoff: u32,
-}
+impl Property {
+    fn load(property_addr: u64, strings: &StringMap) -> Property {
+        let length = read_be_u32(property_addr);
+        let nameoff = read_be_u32(property_addr + 4);
+        let data = unsafe {
+            core::slice::from_raw_parts((property_addr + 8) as *const u8, length as usize)
+        };
+        Property {
+            name: strings.get(nameoff),
+            value: data.to_vec(),
+        }
+    }
+
+    pub fn as_u32(&self) -> Option<u32> {
+        let bytes: [u8; 4] = self.value.as_slice().try_into().ok()?;
+        Some(u32::from_be_bytes(bytes))
+    }
 
-impl PropertyHeader {
-    fn load(addr: u32) -> PropertyHeader {
-        let header = unsafe { &*(addr as *const PropertyHeader) };
-        PropertyHeader {
-            length: header.length.swap_bytes(),
-            nameoff: header.nameoff.swap_bytes(),
+    // Accepts one or two cells
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.value.len() {
+            4 => self.as_u32().map(|value| value as u64),
+            8 => Some(u64::from_be_bytes(self.value.as_slice().try_into().ok()?)),
+            _ => None,
         }
     }
-}
 
-#[derive(Debug)]
-pub struct Property {
-    length: u32,
-    pub name: String,
-    pub value: PropValue,
-}
+    pub fn as_u32s(&self) -> Vec<u32> {
+        self.value
+            .chunks_exact(4)
+            .map(|cell| u32::from_be_bytes([cell[0], cell[1], cell[2], cell[3]]))
+            .collect()
+    }
 
-impl Property {
-    fn load(property_addr: u32, strings: &StringMap) -> Property {
-        let header = PropertyHeader::load(property_addr);
-        let name = strings.get(header.nameoff);
-        let value = match header.length {
-            4 | 8 => {
-                let value = unsafe { core::ptr::read_volatile((property_addr + 8) as *const u32) }
-                    .swap_bytes();
-                PropValue::Integer(value)
-            }
-            _ => {
-                let value = read_string(property_addr + 8);
-                PropValue::String(value)
-            }
+    // The first string of the property
+    pub fn as_str(&self) -> Option<&str> {
+        self.as_strings().next()
+    }
+
+    pub fn as_strings(&self) -> impl Iterator<Item = &str> {
+        let value = self.value.strip_suffix(&[0]).unwrap_or(&self.value);
+        let strings = if value.is_empty() {
+            None
+        } else {
+            Some(value.split(|&c| c == 0))
         };
-        Property {
-            length: header.length,
-            name,
-            value,
+        strings
+            .into_iter()
+            .flatten()
+            .filter_map(|s| core::str::from_utf8(s).ok())
+    }
+
+    // Split into (address, size) pairs of the given cell counts
+    pub fn as_pairs(&self, address_cells: u32, size_cells: u32) -> Vec<(u64, u64)> {
+        let (address_cells, size_cells) = (address_cells as usize, size_cells as usize);
+        let entry = address_cells + size_cells;
+        if entry == 0 {
+            return Vec::new();
         }
+        self.as_u32s()
+            .chunks_exact(entry)
+            .map(|pair| {
+                (
+                    cells_to_u64(&pair[..address_cells]),
+                    cells_to_u64(&pair[address_cells..]),
+                )
+            })
+            .collect()
+    }
+
+    // Whether the value looks like a list of printable strings
+    pub fn is_string_list(&self) -> bool {
+        !self.value.is_empty()
+            && self.value.last() == Some(&0)
+            && self.value[0] != 0
+            && !self.value.windows(2).any(|w| w == [0, 0])
+            && self
+                .value
+                .iter()
+                .all(|&c| c == 0 || (0x20..0x7f).contains(&c))
     }
 }
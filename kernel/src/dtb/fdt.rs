@@ -14,7 +14,7 @@ pub struct FdtHeader {
 }
 
 impl FdtHeader {
-    pub fn load(dtb_addr: u32) -> FdtHeader {
+    pub fn load(dtb_addr: u64) -> FdtHeader {
         let header = unsafe { &*(dtb_addr as *const FdtHeader) };
         FdtHeader {
             magic: header.magic.swap_bytes(),
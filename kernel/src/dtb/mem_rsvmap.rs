@@ -6,7 +6,7 @@ pub struct MemRsvMap {
 }
 
 impl MemRsvMap {
-    pub fn load(mem_rsvmap_addr: u32) -> MemRsvMap {
+    pub fn load(mem_rsvmap_addr: u64) -> MemRsvMap {
         let mut mem_rsv_map = Vec::new();
         let mut mem_rsv_addr = mem_rsvmap_addr;
         loop {
@@ -15,7 +15,7 @@ impl MemRsvMap {
                 break;
             }
             mem_rsv_map.push(mem_rsv);
-            mem_rsv_addr += core::mem::size_of::<MemRsv>() as u32;
+            mem_rsv_addr += core::mem::size_of::<MemRsv>() as u64;
         }
         MemRsvMap { mem_rsv_map }
     }
@@ -29,7 +29,7 @@ pub struct MemRsv {
 }
 
 impl MemRsv {
-    pub fn load(mem_rsv_addr: u32) -> MemRsv {
+    pub fn load(mem_rsv_addr: u64) -> MemRsv {
         let mem_rsv = unsafe { &*(mem_rsv_addr as *const MemRsv) };
         MemRsv {
             addr: mem_rsv.addr.swap_bytes() as u64,
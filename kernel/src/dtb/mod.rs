@@ -4,49 +4,89 @@ mod mem_rsvmap;
 mod strings;
 mod utils;
 
+pub use dt::{DeviceTree, Node, Property};
+
+use crate::mmu::phys_to_virt;
+use alloc::string::String;
 use alloc::vec::Vec;
-use stdio::println;
+use stdio::{debug, trace};
 
 const DTB_ADDRERSS: u64 = 0x6_f000;
 
-pub fn load_dtb() -> dt::Dt {
+static mut DEVICE_TREE: Option<DeviceTree> = None;
+
+// Parse the DTB the bootloader left behind, every lookup afterwards uses this copy
+pub fn init() {
     let (dtb_addr, header) = get_dtb_addr();
-    let strings_addr = dtb_addr + header.off_dt_strings;
-    let strings = strings::StringMap::load(strings_addr);
-    let dt_struct_addr = dtb_addr + header.off_dt_struct;
-    let dt = dt::Dt::load(dt_struct_addr, &strings);
-    dt
+    let dtb = phys_to_virt(dtb_addr as u64);
+    let strings = strings::StringMap::load(dtb + header.off_dt_strings as u64);
+    let mem_rsvmap = mem_rsvmap::MemRsvMap::load(dtb + header.off_mem_rsvmap as u64);
+    let tree = DeviceTree::load(
+        dtb + header.off_dt_struct as u64,
+        &strings,
+        mem_rsvmap.mem_rsv_map,
+    );
+    debug!("Loaded device tree with {} nodes", tree.nodes().count());
+    unsafe {
+        DEVICE_TREE = Some(tree);
+    }
+}
+
+pub fn get() -> &'static DeviceTree {
+    unsafe {
+        (*core::ptr::addr_of!(DEVICE_TREE))
+            .as_ref()
+            .expect("Device tree not loaded")
+    }
 }
 
+// The physical address of the DTB and its header
 pub fn get_dtb_addr() -> (u32, fdt::FdtHeader) {
-    let dtb_addr = DTB_ADDRERSS as *const u8;
-    println!("DTB address: {:p}", dtb_addr);
+    let dtb_addr = phys_to_virt(DTB_ADDRERSS) as *const u8;
     let dtb_addr = unsafe { core::ptr::read_volatile(dtb_addr as *const u32) };
-    println!("DTB address: {:#x}", dtb_addr);
-    let header = fdt::FdtHeader::load(dtb_addr);
+    trace!("DTB address: {:#x}", dtb_addr);
+    let header = fdt::FdtHeader::load(phys_to_virt(dtb_addr as u64));
     assert!(header.magic == 0xd00dfeed);
     (dtb_addr, header)
 }
 
 pub fn get_initrd_start() -> u32 {
-    let dt = load_dtb();
-    let node = dt.get("linux,initrd-start");
-    match node {
-        Some(node) => match node.value {
-            dt::PropValue::Integer(value) => value,
-            _ => panic!("Invalid initrd-start value"),
-        },
-        None => panic!("Failed to get initrd start address!"),
-    }
+    get()
+        .find("/chosen")
+        .and_then(|chosen| chosen.property("linux,initrd-start"))
+        .and_then(|prop| prop.as_u64())
+        .expect("Failed to get initrd start address!") as u32
 }
 
-pub fn get_reserved_memory() -> Vec<(u32, u32)> {
-    let (dtb_addr, header) = get_dtb_addr();
-    let mem_rsvmap_addr = dtb_addr + header.off_mem_rsvmap;
-    let mem_rsvmap = mem_rsvmap::MemRsvMap::load(mem_rsvmap_addr);
-    let mut ret = Vec::new();
-    for mem_rsv in mem_rsvmap.mem_rsv_map {
-        ret.push((mem_rsv.addr as u32, mem_rsv.size as u32));
+// The kernel command line in /chosen/bootargs
+pub fn get_bootargs() -> Option<String> {
+    let prop = get().find("/chosen")?.property("bootargs")?;
+    prop.as_str().map(String::from)
+}
+
+pub fn get_initrd_end() -> Option<u32> {
+    get()
+        .find("/chosen")?
+        .property("linux,initrd-end")?
+        .as_u64()
+        .map(|end| end as u32)
+}
+
+// The /memreserve/ entries of the header and the static regions under
+// /reserved-memory, as (address, size)
+pub fn get_reserved_memory() -> Vec<(u64, u64)> {
+    let tree = get();
+    let mut reserved: Vec<(u64, u64)> = tree
+        .mem_rsvmap
+        .iter()
+        .map(|mem_rsv| (mem_rsv.addr, mem_rsv.size))
+        .collect();
+    if let Some(node) = tree.find("/reserved-memory") {
+        reserved.extend(
+            node.children()
+                .filter(|child| child.is_enabled())
+                .flat_map(|child| child.regions()),
+        );
     }
-    ret
+    reserved
 }
@@ -1,15 +1,15 @@
 use super::utils::read_string;
 use alloc::string::String;
 pub struct StringMap {
-    base: u32,
+    base: u64,
 }
 
 impl StringMap {
-    pub fn load(base: u32) -> StringMap {
+    pub fn load(base: u64) -> StringMap {
         StringMap { base }
     }
 
     pub fn get(&self, offset: u32) -> String {
-        read_string(self.base + offset)
+        read_string(self.base + offset as u64)
     }
 }
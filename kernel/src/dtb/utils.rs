@@ -1,7 +1,7 @@
 use alloc::string::String;
 
 // read untill null byte
-pub fn read_string(addr: u32) -> String {
+pub fn read_string(addr: u64) -> String {
     let mut addr = addr;
     let mut string = String::new();
     loop {
@@ -14,3 +14,8 @@ pub fn read_string(addr: u32) -> String {
     }
     string
 }
+
+// The structure block stores every integer big endian
+pub fn read_be_u32(addr: u64) -> u32 {
+    unsafe { core::ptr::read_volatile(addr as *const u32) }.swap_bytes()
+}
@@ -1,24 +1,32 @@
+use crate::device::local_intc;
 use crate::exception::trap_frame;
 use crate::exception::Mmio;
-use core::ptr::read_volatile;
 use driver::mmio::regs::AuxReg;
 use driver::mmio::regs::MmioReg;
 
+// Service the pending core timer and mini UART interrupts
+pub unsafe fn dispatch() {
+    if local_intc::core0_irq_source() & local_intc::CNTPNSIRQ != 0 {
+        {
+            let tm = crate::timer::manager::get();
+            // debug!("Timer interrupt at {:#?}", tm.current_time());
+            tm.handle_interrupt();
+        }
+    }
+    if Mmio::read_reg(MmioReg::Aux(AuxReg::Irq)) & 0x1 == 0x1 {
+        driver::uart::handle_irq();
+    }
+}
+
 #[no_mangle]
 unsafe fn irq_handler(eidx: u64, sp: u64) {
     trap_frame::TRAP_FRAME = Some(trap_frame::TrapFrame::new(sp));
     match eidx {
-        5 | 9 => {
-            if read_volatile(0x4000_0060 as *const u32) == 0x02 {
-                {
-                    let tm = crate::timer::manager::get();
-                    // debug!("Timer interrupt at {:#?}", tm.current_time());
-                    tm.handle_interrupt();
-                }
-            }
-            if Mmio::read_reg(MmioReg::Aux(AuxReg::Irq)) & 0x1 == 0x1 {
-                driver::uart::handle_irq();
-            }
+        5 => dispatch(),
+        9 => {
+            dispatch();
+            crate::oom::check();
+            crate::signal::do_signal();
         }
         _ => {
             panic!("Unknown interrupt")
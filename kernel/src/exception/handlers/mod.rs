@@ -1,3 +1,3 @@
-mod irq;
+pub mod irq;
 mod page;
 mod svc;
@@ -1,7 +1,14 @@
+use super::super::trap_frame;
+use crate::scheduler;
+use crate::signal;
 use core::arch::asm;
-use stdio::println;
+use stdio::error;
 
-pub unsafe fn page_fault() {
+// Instruction and data aborts taken from EL0
+const EC_IABT_LOWER: u64 = 0b100000;
+const EC_DABT_LOWER: u64 = 0b100100;
+
+pub unsafe fn page_fault(sp: u64) {
     let esr_el1: u64;
     asm!(
         "mrs {0}, esr_el1",
@@ -19,10 +26,36 @@ pub unsafe fn page_fault() {
         out(reg) elr_el1,
     );
 
-    println!("Page fault");
-    println!("Exception Class: 0b{:06b}", ec);
-    println!("ESR_EL1: 0x{:x}", esr_el1);
-    println!("ELR_EL1: 0x{:x}", elr_el1);
-    println!("FAR_EL1: 0x{:x}", far_el1);
+    // A bad access from EL0 only ends the faulting thread
+    if ec == EC_IABT_LOWER || ec == EC_DABT_LOWER {
+        let thread = scheduler::get().current_thread();
+        if thread.in_stack_guard(far_el1) {
+            error!("stack overflow in thread {}", thread.id);
+        } else {
+            error!(
+                "segmentation fault in thread {} at 0x{:x}, address 0x{:x}",
+                thread.id, elr_el1, far_el1
+            );
+        }
+        kill_current(sp, signal::SIGSEGV);
+        return;
+    }
+
+    error!("Page fault");
+    error!("Exception Class: 0b{:06b}", ec);
+    error!("ESR_EL1: 0x{:x}", esr_el1);
+    error!("ELR_EL1: 0x{:x}", elr_el1);
+    error!("FAR_EL1: 0x{:x}", far_el1);
     panic!("Page fault");
 }
+
+// Signal the faulting thread, returning to its handler or to whichever
+// thread runs next
+unsafe fn kill_current(sp: u64, sig: usize) {
+    trap_frame::TRAP_FRAME = Some(trap_frame::TrapFrame::new(sp));
+    let tid = scheduler::get().current.unwrap();
+    signal::force(tid, sig);
+    signal::do_signal();
+    trap_frame::TRAP_FRAME.unwrap().restore();
+    trap_frame::TRAP_FRAME = None;
+}
@@ -1,7 +1,9 @@
 use super::page::page_fault;
-use crate::{exception::trap_frame, mmu::vm::VirtualMemory};
+use crate::exception::trap_frame;
+use crate::file::{STDIN, STDOUT};
+use abi::{Errno, Sysno};
 use core::{arch::asm, fmt::Debug};
-use stdio::{debug, println};
+use stdio::{debug, error};
 
 #[repr(C)]
 #[derive(Clone, Copy)]
@@ -66,11 +68,11 @@ unsafe fn lower_exception_handler(eidx: u64, sp: u64) {
         0b001110 => {
             panic!("Illegal Execution state.");
         }
-        0b100000 | 0b100001 | 0b100100 => page_fault(),
+        0b100000 | 0b100001 | 0b100100 | 0b100101 => page_fault(sp),
         _ => {
-            println!("Exception {}", eidx);
-            println!("Unknown exception");
-            println!("ec: 0b{:06b}", ec);
+            error!("Exception {}", eidx);
+            error!("Unknown exception");
+            error!("ec: 0b{:06b}", ec);
             el1_interrupt(sp);
         }
     }
@@ -79,6 +81,8 @@ unsafe fn lower_exception_handler(eidx: u64, sp: u64) {
 unsafe fn svc_handler(sp: u64) {
     trap_frame::TRAP_FRAME = Some(trap_frame::TrapFrame::new(sp));
     syscall_handler(sp);
+    crate::oom::check();
+    crate::signal::do_signal();
     trap_frame::TRAP_FRAME.unwrap().restore();
     trap_frame::TRAP_FRAME = None;
 }
@@ -102,74 +106,222 @@ unsafe fn el1_interrupt(sp: u64) {
     if esr_el1 == 0x5600_0000 {
         let syscall = Syscall::new(sp);
         debug!("Syscall idx: {}", syscall.idx);
-        println!("syscall: {:?}", syscall);
+        error!("syscall: {:?}", syscall);
     }
     panic!("Segmentation fault");
 }
 
+// Handlers return None when x0 must be left untouched, e.g. when the
+// calling thread was switched out and the trap frame belongs to another one
+type Handler = unsafe fn(&Syscall) -> Option<u64>;
+
+const SYSCALL_TABLE: [(Sysno, Handler); Sysno::ALL.len()] = [
+    (Sysno::GetPid, sys_get_pid),
+    (Sysno::UartRead, sys_uart_read),
+    (Sysno::UartWrite, sys_uart_write),
+    (Sysno::Exec, sys_exec),
+    (Sysno::Fork, sys_fork),
+    (Sysno::Exit, sys_exit),
+    (Sysno::MboxCall, sys_mbox_call),
+    (Sysno::Kill, sys_kill),
+    (Sysno::Sigaction, sys_sigaction),
+    (Sysno::SignalKill, sys_signal_kill),
+    (Sysno::Sigprocmask, sys_sigprocmask),
+    (Sysno::Alarm, sys_alarm),
+    (Sysno::Setitimer, sys_setitimer),
+    (Sysno::Pipe, sys_pipe),
+    (Sysno::Read, sys_read),
+    (Sysno::Write, sys_write),
+    (Sysno::Close, sys_close),
+    (Sysno::Dup2, sys_dup2),
+    (Sysno::Open, sys_open),
+    (Sysno::Mmap, sys_mmap),
+    (Sysno::Munmap, sys_munmap),
+    (Sysno::Mprotect, sys_mprotect),
+    (Sysno::Brk, sys_brk),
+    (Sysno::Sbrk, sys_sbrk),
+    (Sysno::Wait, sys_wait),
+    (Sysno::Procinfo, sys_procinfo),
+    (Sysno::Nanosleep, sys_nanosleep),
+    (Sysno::ClockGettime, sys_clock_gettime),
+    (Sysno::Syslog, sys_syslog),
+    (Sysno::Sigreturn, sys_sigreturn),
+];
+
+// Syscall numbers run up to sigreturn, which keeps the Linux one
+const SYSCALL_COUNT: usize = Sysno::Sigreturn as usize + 1;
+
+// The handlers indexed by syscall number, None for the unused numbers
+const SYSCALLS: [Option<Handler>; SYSCALL_COUNT] = by_number(SYSCALL_TABLE);
+
+const fn by_number(
+    table: [(Sysno, Handler); Sysno::ALL.len()],
+) -> [Option<Handler>; SYSCALL_COUNT] {
+    let mut handlers: [Option<Handler>; SYSCALL_COUNT] = [None; SYSCALL_COUNT];
+    let mut i = 0;
+    while i < table.len() {
+        let (sysno, handler) = table[i];
+        assert!(handlers[sysno as usize].is_none(), "Duplicate syscall");
+        handlers[sysno as usize] = Some(handler);
+        i += 1;
+    }
+    handlers
+}
+
 unsafe fn syscall_handler(sp: u64) {
     let syscall = Syscall::new(sp);
     assert!(trap_frame::TRAP_FRAME.is_some());
-    let vm = VirtualMemory::load(trap_frame::TRAP_FRAME.as_ref().unwrap().state.l0);
-    match syscall.idx {
-        0 => {
-            // println!("Syscall get_pid");
-            let pid = crate::syscall::get_pid();
-            trap_frame::TRAP_FRAME.as_mut().unwrap().state.x[0] = pid;
-        }
-        1 => {
-            // println!("Syscall read");
-            let buf = syscall.arg0 as *mut u8;
-            let buf = vm.get_phys(buf as u64);
-            let size = syscall.arg1 as usize;
-            let read = crate::syscall::read(buf, size);
-            assert!(size == 1);
-            if read == 0 {
-                trap_frame::TRAP_FRAME.as_mut().unwrap().state.pc -= 4;
-            } else {
-                trap_frame::TRAP_FRAME.as_mut().unwrap().state.x[0] = read as u64;
-            }
-        }
-        2 => {
-            // println!("Syscall write");
-            let buf = syscall.arg0 as *const u8;
-            let buf = vm.get_phys(buf as u64);
-            let size = syscall.arg1 as usize;
-            let written = crate::syscall::write(buf, size);
-            trap_frame::TRAP_FRAME.as_mut().unwrap().state.x[0] = written as u64;
-        }
-        3 => {
-            // println!("Syscall exec");
-            let name = syscall.arg0 as *const u8;
-            let name = vm.get_phys(name as u64);
-            let ret = crate::syscall::exec(name);
-            trap_frame::TRAP_FRAME.as_mut().unwrap().state.x[0] = ret;
-        }
-        4 => {
-            // println!("Syscall fork");
-            let pid = crate::syscall::fork();
-            trap_frame::TRAP_FRAME.as_mut().unwrap().state.x[0] = pid;
-        }
-        5 => {
-            // println!("Syscall exit");
-            crate::syscall::exit(syscall.arg0);
-        }
-        6 => {
-            // println!("Syscall mbox_call");
-            let channel = syscall.arg0 as u8;
-            let mbox = syscall.arg1 as *mut u32;
-            let mbox = vm.get_phys(mbox as u64) as *mut u32;
-            let ret = crate::syscall::mbox_call(channel, mbox);
-            trap_frame::TRAP_FRAME.as_mut().unwrap().state.x[0] = ret as u64;
-        }
-        7 => {
-            // println!("Syscall kill");
-            let pid = syscall.arg0;
-            crate::syscall::kill(pid);
-        }
-        _ => {
-            println!("Unknown syscall: 0x{:x}", syscall.idx);
-            panic!("Unknown syscall");
+    let ret = match SYSCALLS.get(syscall.idx as usize).copied().flatten() {
+        Some(handler) => handler(&syscall),
+        None => {
+            debug!("Unknown syscall: 0x{:x}", syscall.idx);
+            Some(Errno::ENOSYS.to_ret())
         }
+    };
+    if let Some(ret) = ret {
+        trap_frame::TRAP_FRAME.as_mut().unwrap().state.x[0] = ret;
     }
 }
+
+unsafe fn sys_get_pid(_: &Syscall) -> Option<u64> {
+    Some(crate::syscall::get_pid())
+}
+
+unsafe fn sys_uart_read(syscall: &Syscall) -> Option<u64> {
+    crate::syscall::read(STDIN, syscall.arg0, syscall.arg1 as usize)
+}
+
+unsafe fn sys_uart_write(syscall: &Syscall) -> Option<u64> {
+    crate::syscall::write(STDOUT, syscall.arg0, syscall.arg1 as usize)
+}
+
+unsafe fn sys_exec(syscall: &Syscall) -> Option<u64> {
+    crate::syscall::exec(syscall.arg0, syscall.arg1, syscall.arg2)
+}
+
+unsafe fn sys_fork(_: &Syscall) -> Option<u64> {
+    Some(crate::syscall::fork())
+}
+
+unsafe fn sys_exit(syscall: &Syscall) -> Option<u64> {
+    crate::syscall::exit(syscall.arg0);
+    None
+}
+
+unsafe fn sys_mbox_call(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::mbox_call(syscall.arg0 as u8, syscall.arg1))
+}
+
+unsafe fn sys_kill(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::kill(syscall.arg0))
+}
+
+unsafe fn sys_sigaction(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::sigaction(syscall.arg0, syscall.arg1))
+}
+
+unsafe fn sys_signal_kill(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::signal_kill(syscall.arg0, syscall.arg1))
+}
+
+unsafe fn sys_sigprocmask(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::sigprocmask(syscall.arg0, syscall.arg1))
+}
+
+unsafe fn sys_alarm(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::alarm(syscall.arg0))
+}
+
+unsafe fn sys_setitimer(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::setitimer(syscall.arg0, syscall.arg1))
+}
+
+unsafe fn sys_pipe(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::pipe(syscall.arg0))
+}
+
+unsafe fn sys_read(syscall: &Syscall) -> Option<u64> {
+    crate::syscall::read(syscall.arg0 as usize, syscall.arg1, syscall.arg2 as usize)
+}
+
+unsafe fn sys_write(syscall: &Syscall) -> Option<u64> {
+    crate::syscall::write(syscall.arg0 as usize, syscall.arg1, syscall.arg2 as usize)
+}
+
+unsafe fn sys_close(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::close(syscall.arg0 as usize))
+}
+
+unsafe fn sys_dup2(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::dup2(
+        syscall.arg0 as usize,
+        syscall.arg1 as usize,
+    ))
+}
+
+unsafe fn sys_open(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::open(syscall.arg0))
+}
+
+unsafe fn sys_mmap(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::mmap(
+        syscall.arg0,
+        syscall.arg1 as usize,
+        syscall.arg2,
+        syscall.arg3,
+        syscall.arg4 as usize,
+        syscall.arg5 as usize,
+    ))
+}
+
+unsafe fn sys_munmap(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::munmap(syscall.arg0, syscall.arg1 as usize))
+}
+
+unsafe fn sys_mprotect(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::mprotect(
+        syscall.arg0,
+        syscall.arg1 as usize,
+        syscall.arg2,
+    ))
+}
+
+unsafe fn sys_brk(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::brk(syscall.arg0))
+}
+
+unsafe fn sys_sbrk(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::sbrk(syscall.arg0 as i64))
+}
+
+unsafe fn sys_wait(syscall: &Syscall) -> Option<u64> {
+    crate::syscall::wait(syscall.arg0 as i64, syscall.arg1, syscall.arg2)
+}
+
+unsafe fn sys_procinfo(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::procinfo(
+        syscall.arg0,
+        syscall.arg1 as usize,
+    ))
+}
+
+unsafe fn sys_nanosleep(syscall: &Syscall) -> Option<u64> {
+    crate::syscall::nanosleep(syscall.arg0)
+}
+
+unsafe fn sys_clock_gettime(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::clock_gettime(syscall.arg0, syscall.arg1))
+}
+
+unsafe fn sys_syslog(syscall: &Syscall) -> Option<u64> {
+    Some(crate::syscall::syslog(
+        syscall.arg0,
+        syscall.arg1,
+        syscall.arg2,
+    ))
+}
+
+unsafe fn sys_sigreturn(_: &Syscall) -> Option<u64> {
+    crate::syscall::sigreturn();
+    None
+}
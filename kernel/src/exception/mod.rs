@@ -1,12 +1,38 @@
 mod handlers;
 pub mod trap_frame;
 
+use crate::mmu::config::KERNEL_STACK_SIZE;
 use core::arch::{asm, global_asm};
 use driver::mmio::Mmio;
-use stdio::debug;
+use stdio::{debug, error};
+
+pub use handlers::irq::dispatch as irq_dispatch;
+
+const OVERFLOW_STACK_SIZE: usize = 0x4000;
+
+#[repr(C, align(16))]
+struct OverflowStack([u8; OVERFLOW_STACK_SIZE]);
+
+// Where a kernel stack overflow is reported from
+static mut OVERFLOW_STACK: OverflowStack = OverflowStack([0; OVERFLOW_STACK_SIZE]);
 
 global_asm!(include_str!("context_switch.S"));
-global_asm!(include_str!("exception_table.S"));
+global_asm!(
+    include_str!("exception_table.S"),
+    KERNEL_STACK_SIZE = const KERNEL_STACK_SIZE,
+    OVERFLOW_STACK = sym OVERFLOW_STACK,
+    OVERFLOW_STACK_SIZE = const OVERFLOW_STACK_SIZE,
+);
+
+#[no_mangle]
+unsafe extern "C" fn kernel_stack_overflow(far: u64) -> ! {
+    let elr_el1: u64;
+    asm!(
+        "mrs {0}, elr_el1", out(reg) elr_el1,
+    );
+    error!("ELR_EL1: 0x{:x}", elr_el1);
+    panic!("Kernel stack overflow, fault at 0x{:x}", far);
+}
 
 #[no_mangle]
 unsafe fn unknown_exception_handler(eidx: u64) {
@@ -0,0 +1,77 @@
+pub mod initramfs;
+pub mod pipe;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+const MAX_FILES: usize = 16;
+
+pub const STDIN: usize = 0;
+pub const STDOUT: usize = 1;
+
+#[derive(Clone, Debug)]
+pub enum File {
+    Console,
+    Initramfs(initramfs::InitramfsFile),
+    PipeRead(pipe::PipeReader),
+    PipeWrite(pipe::PipeWriter),
+}
+
+#[derive(Clone, Debug)]
+pub struct FileTable {
+    files: Vec<Option<File>>,
+}
+
+impl FileTable {
+    // stdin, stdout and stderr all start out on the console
+    pub fn new() -> Self {
+        FileTable {
+            files: vec![
+                Some(File::Console),
+                Some(File::Console),
+                Some(File::Console),
+            ],
+        }
+    }
+
+    pub fn get(&self, fd: usize) -> Option<&File> {
+        self.files.get(fd).and_then(|f| f.as_ref())
+    }
+
+    // Install the file at the lowest free descriptor
+    pub fn insert(&mut self, file: File) -> Option<usize> {
+        match self.files.iter().position(|f| f.is_none()) {
+            Some(fd) => {
+                self.files[fd] = Some(file);
+                Some(fd)
+            }
+            None if self.files.len() < MAX_FILES => {
+                self.files.push(Some(file));
+                Some(self.files.len() - 1)
+            }
+            None => None,
+        }
+    }
+
+    // Install the file at the given descriptor, closing what was there
+    pub fn set(&mut self, fd: usize, file: File) -> bool {
+        if fd >= MAX_FILES {
+            return false;
+        }
+        if fd >= self.files.len() {
+            self.files.resize(fd + 1, None);
+        }
+        self.files[fd] = Some(file);
+        true
+    }
+
+    pub fn close(&mut self, fd: usize) -> bool {
+        match self.files.get_mut(fd) {
+            Some(file) if file.is_some() => {
+                *file = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use crate::INITRAMFS_ADDR;
+use alloc::rc::Rc;
+use core::cell::Cell;
+use filesystem::cpio::CpioArchive;
+
+// A read-only file of the initramfs, the offset is shared between duplicates
+#[derive(Clone, Debug)]
+pub struct InitramfsFile {
+    data: &'static [u8],
+    offset: Rc<Cell<usize>>,
+}
+
+impl InitramfsFile {
+    pub fn open(name: &str) -> Option<Self> {
+        let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
+        let data = rootfs.get_file(name)?;
+        // The initramfs stays mapped and is never freed
+        let data = unsafe { core::slice::from_raw_parts(data.as_ptr(), data.len()) };
+        Some(InitramfsFile {
+            data,
+            offset: Rc::new(Cell::new(0)),
+        })
+    }
+
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        let offset = self.offset.get();
+        let len = buf.len().min(self.data.len() - offset);
+        buf[..len].copy_from_slice(&self.data[offset..offset + len]);
+        self.offset.set(offset + len);
+        len
+    }
+
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+}
@@ -0,0 +1,125 @@
+use crate::scheduler;
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+const PIPE_SIZE: usize = 0x1000;
+
+#[derive(Debug)]
+struct Pipe {
+    buf: VecDeque<u8>,
+    readers: usize,
+    writers: usize,
+    read_waiters: Vec<usize>,
+    write_waiters: Vec<usize>,
+}
+
+fn wake_all(waiters: &mut Vec<usize>) {
+    for tid in waiters.drain(..) {
+        scheduler::get().wake(tid);
+    }
+}
+
+#[derive(Debug)]
+pub enum PipeError {
+    WouldBlock,
+    BrokenPipe,
+}
+
+#[derive(Debug)]
+pub struct PipeReader(Rc<RefCell<Pipe>>);
+
+#[derive(Debug)]
+pub struct PipeWriter(Rc<RefCell<Pipe>>);
+
+pub fn new() -> (PipeReader, PipeWriter) {
+    let pipe = Rc::new(RefCell::new(Pipe {
+        buf: VecDeque::with_capacity(PIPE_SIZE),
+        readers: 1,
+        writers: 1,
+        read_waiters: Vec::new(),
+        write_waiters: Vec::new(),
+    }));
+    (PipeReader(pipe.clone()), PipeWriter(pipe))
+}
+
+impl PipeReader {
+    // Reading an empty pipe returns EOF only once every writer is closed
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize, PipeError> {
+        let mut pipe = self.0.borrow_mut();
+        if pipe.buf.is_empty() {
+            return if pipe.writers == 0 {
+                Ok(0)
+            } else {
+                Err(PipeError::WouldBlock)
+            };
+        }
+        let len = buf.len().min(pipe.buf.len());
+        for c in buf.iter_mut().take(len) {
+            *c = pipe.buf.pop_front().unwrap();
+        }
+        wake_all(&mut pipe.write_waiters);
+        Ok(len)
+    }
+
+    pub fn wait(&self, tid: usize) {
+        self.0.borrow_mut().read_waiters.push(tid);
+    }
+}
+
+impl PipeWriter {
+    pub fn write(&self, buf: &[u8]) -> Result<usize, PipeError> {
+        let mut pipe = self.0.borrow_mut();
+        if pipe.readers == 0 {
+            return Err(PipeError::BrokenPipe);
+        }
+        let len = buf.len().min(PIPE_SIZE - pipe.buf.len());
+        if len == 0 && !buf.is_empty() {
+            return Err(PipeError::WouldBlock);
+        }
+        pipe.buf.extend(&buf[..len]);
+        wake_all(&mut pipe.read_waiters);
+        Ok(len)
+    }
+
+    pub fn wait(&self, tid: usize) {
+        self.0.borrow_mut().write_waiters.push(tid);
+    }
+}
+
+impl Clone for PipeReader {
+    fn clone(&self) -> Self {
+        self.0.borrow_mut().readers += 1;
+        PipeReader(self.0.clone())
+    }
+}
+
+impl Clone for PipeWriter {
+    fn clone(&self) -> Self {
+        self.0.borrow_mut().writers += 1;
+        PipeWriter(self.0.clone())
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        let mut pipe = self.0.borrow_mut();
+        pipe.readers -= 1;
+        if pipe.readers == 0 {
+            // Blocked writers get EPIPE once they retry
+            wake_all(&mut pipe.write_waiters);
+        }
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        let mut pipe = self.0.borrow_mut();
+        pipe.writers -= 1;
+        if pipe.writers == 0 {
+            // Blocked readers see EOF once they retry
+            wake_all(&mut pipe.read_waiters);
+        }
+    }
+}
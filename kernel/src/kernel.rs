@@ -1,17 +1,20 @@
+use crate::mmu::config::DEVICE_BASE;
 use core::arch::{asm, global_asm};
 use driver::uart::init;
-use stdio::println;
+use stdio::{debug, info};
 
 global_asm!(include_str!("kernel.S"));
 
 #[no_mangle]
 extern "C" fn _start_rust() {
+    // The peripherals are only reachable through the device mapping
+    driver::mmio::regs::relocate(DEVICE_BASE as usize);
     init();
-    println!("Kernel starting main...");
+    info!("Kernel starting main...");
     let sp: u64;
     unsafe {
         asm!("mov {}, sp", out(reg) sp);
     }
-    println!("Stack pointer: {:#x}", sp);
+    debug!("Stack pointer: {:#x}", sp);
     crate::main();
 }
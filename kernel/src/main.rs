@@ -8,41 +8,69 @@ extern crate alloc;
 
 mod allocator;
 mod commands;
+mod device;
 mod dtb;
 mod exception;
+mod file;
 mod kernel;
 mod mmu;
+mod oom;
 mod panic;
+mod param;
 mod scheduler;
+mod shell;
+mod signal;
 mod syscall;
 mod thread;
 mod timer;
 
 use allocator::buddy::BUDDY_SYSTEM;
-use stdio::{debug, gets, print, println};
+use filesystem::cpio::CpioArchive;
+use stdio::{debug, info, warn};
 
-pub static mut INITRAMFS_ADDR: u32 = 0;
+// Where the kernel reaches the initramfs, in the linear map
+pub static mut INITRAMFS_ADDR: u64 = 0;
 
 fn main() -> ! {
     boot();
-    println!("Kernel booted successfully!");
-    // commands::execute(b"exec vm.img");
-    kernel_shell();
+    info!("Kernel booted successfully!");
+    start_init();
+    shell::run();
 }
 
-fn kernel_shell() -> ! {
-    const MAX_COMMAND_LEN: usize = 0x100;
-    let mut buf: [u8; MAX_COMMAND_LEN] = [0; MAX_COMMAND_LEN];
-    loop {
-        print!("> ");
-        let len = gets(&mut buf);
-        assert_eq!(buf[len], 0);
-        commands::execute(&buf);
-    }
+// Run init= from the initramfs as PID 1, returning only if it is not found
+fn start_init() {
+    let init = &param::get().init;
+    let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
+    let data = match rootfs.get_file(init) {
+        Some(data) => data,
+        None => {
+            warn!("No {} found, falling back to the kernel shell", init);
+            return;
+        }
+    };
+    let sched = scheduler::get();
+    let tid = match sched.create_thread(data, &[init.clone()]) {
+        Ok(tid) => tid,
+        Err(errno) => {
+            warn!(
+                "Cannot run {}: {}, falling back to the kernel shell",
+                init, errno
+            );
+            return;
+        }
+    };
+    assert_eq!(tid, scheduler::INIT_PID);
+    sched.run_threads();
 }
 
 fn boot() {
-    println!("Hello, world!");
+    dtb::init();
+    param::init();
+    if let Some(level) = param::get().loglevel {
+        stdio::log::set_console_level(level);
+    }
+    device::probe();
     print_mailbox_info();
     initramfs_init();
     buddy_init();
@@ -52,61 +80,23 @@ fn boot() {
 }
 
 fn print_mailbox_info() {
-    println!("Printing mailbox info...");
     let revision = driver::mailbox::get_board_revision();
-    println!("Board revision: {:x}", revision);
-    let (lb, ub) = driver::mailbox::get_arm_memory();
-    println!("ARM memory: {:x} - {:x}", lb, ub);
+    info!("Board revision: {:x}", revision);
 }
 
 fn initramfs_init() {
     unsafe {
-        INITRAMFS_ADDR = dtb::get_initrd_start();
+        INITRAMFS_ADDR = mmu::phys_to_virt(dtb::get_initrd_start() as u64);
     }
     debug!("Initramfs address: {:#x}", unsafe { INITRAMFS_ADDR });
 }
 
 fn buddy_init() {
+    allocator::memmap::init();
+    allocator::buddy::set_verbose(param::get().buddy_verbose);
     unsafe {
-        BUDDY_SYSTEM.init();
-    }
-    buddy_reserve_memory();
-    unsafe {
+        BUDDY_SYSTEM.init(allocator::memmap::get());
         BUDDY_SYSTEM.initialized = true;
-        BUDDY_SYSTEM.print_info();
-    }
-}
-
-fn buddy_reserve_memory() {
-    unsafe {
-        BUDDY_SYSTEM.reserve_by_addr_range(0x1000, 0x1_0000);
-    }
-
-    let rsv_mem = dtb::get_reserved_memory();
-    for (addr, size) in rsv_mem {
-        unsafe {
-            BUDDY_SYSTEM.reserve_by_addr_range(addr, addr + size);
-        }
-    }
-
-    unsafe {
-        BUDDY_SYSTEM.reserve_by_addr_range(0x6_0000, 0x8_0000); // kernel stack reserved
-        BUDDY_SYSTEM.reserve_by_addr_range(0x8_0000, 0x10_0000); // kernel code reserved
-    }
-
-    unsafe {
-        // initramfs reserved
-        BUDDY_SYSTEM.reserve_by_addr_range(INITRAMFS_ADDR, INITRAMFS_ADDR + 0x4_0000);
-
-        // bump allocator reserved
-        BUDDY_SYSTEM.reserve_by_addr_range(
-            allocator::config::BUMP_START_ADDR,
-            allocator::config::BUMP_END_ADDR,
-        );
-
-        // rpi3 dtb reserved
-        BUDDY_SYSTEM
-            .reserve_by_addr_range(dtb::get_dtb_addr().0, dtb::get_dtb_addr().0 + 0x10_0000);
     }
 }
 
@@ -114,7 +104,6 @@ fn print_boot_time() {
     let tm = crate::timer::manager::get();
     let now = tm.get_current();
     let freq = tm.get_frequency();
-    println!("Frequency: {} Hz", freq);
-    println!("Current time: {}", now);
-    println!("Boot time: {} ms", now / (freq / 1000));
+    debug!("Frequency: {} Hz", freq);
+    info!("Boot time: {} ms", now / (freq / 1000));
 }
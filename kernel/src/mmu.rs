@@ -1,20 +1,92 @@
 use core::arch::asm;
+mod asid;
 pub mod config;
-mod entry;
+mod descriptor;
 mod page_table;
+pub use page_table::table_pages;
 pub mod vm;
 
+use crate::mmu::config::DEVICE_BASE;
+use crate::mmu::config::DEVICE_END;
+use crate::mmu::config::DEVICE_L1_ADDR;
+use crate::mmu::config::DEVICE_L2_ADDR;
+use crate::mmu::config::GUARD_SIZE;
+use crate::mmu::config::IDLE_L0_ADDR;
+use crate::mmu::config::KERNEL_BASE;
+use crate::mmu::config::KERNEL_DATA_CONFIG;
+use crate::mmu::config::KERNEL_DEVICE_CONFIG;
+use crate::mmu::config::KERNEL_L3_ADDR;
+use crate::mmu::config::KERNEL_L3_COUNT;
+use crate::mmu::config::KERNEL_RODATA_CONFIG;
+use crate::mmu::config::KERNEL_STACK_SIZE;
+use crate::mmu::config::KERNEL_TEXT_CONFIG;
 use crate::mmu::config::L0_ADDR;
 use crate::mmu::config::L1_ADDR;
 use crate::mmu::config::L2_ADDR;
+use crate::mmu::config::LINEAR_MAP_END;
 use crate::mmu::config::MAIR_CONFIG_DEFAULT;
-use crate::mmu::config::MAIR_DEVICE_NG_NR_NE_IDX;
-use crate::mmu::config::MAIR_NORMAL_NC_IDX;
-use crate::mmu::config::PD_ACCESS;
-use crate::mmu::config::PD_BLOCK;
-use crate::mmu::config::PD_TABLE;
+use crate::mmu::config::SCTLR_MMU_CACHES;
 use crate::mmu::config::TCR_CONFIG_DEFAULT;
+use crate::mmu::descriptor::{Descriptor, Flags};
 
+extern "C" {
+    static _start: u8;
+    static __text_end: u8;
+    static __rodata_end: u8;
+}
+
+// Size of an L2 block
+const BLOCK_SIZE: u64 = (1 << 9) * (1 << 12);
+
+// Where the kernel reaches a physical address of the RAM
+pub fn phys_to_virt(pa: u64) -> u64 {
+    assert!(pa < LINEAR_MAP_END, "0x{:x} is not in the linear map", pa);
+    pa + KERNEL_BASE
+}
+
+// The physical address behind a pointer into the linear map or the image
+pub fn virt_to_phys(va: u64) -> u64 {
+    assert!(
+        (KERNEL_BASE..KERNEL_BASE + LINEAR_MAP_END).contains(&va),
+        "0x{:x} is not in the linear map",
+        va
+    );
+    va - KERNEL_BASE
+}
+
+// Where the kernel reaches the registers at a physical address, None outside
+// of the peripherals
+pub fn device_addr(pa: u64) -> Option<u64> {
+    (LINEAR_MAP_END..DEVICE_END)
+        .contains(&pa)
+        .then(|| pa + DEVICE_BASE)
+}
+
+// Physical address of a kernel image symbol. Before the MMU is on the
+// symbol is reached PC relative, at its physical address already.
+fn image_phys(sym: &u8) -> u64 {
+    sym as *const u8 as u64 & !KERNEL_BASE
+}
+
+// The attributes of a page of the linear map by the image section in it
+fn kernel_page_config(pa: u64) -> Flags {
+    let (text_start, text_end, rodata_end) = unsafe {
+        (
+            image_phys(&_start),
+            image_phys(&__text_end),
+            image_phys(&__rodata_end),
+        )
+    };
+    if (text_start..text_end).contains(&pa) {
+        KERNEL_TEXT_CONFIG
+    } else if (text_end..rodata_end).contains(&pa) {
+        KERNEL_RODATA_CONFIG
+    } else {
+        KERNEL_DATA_CONFIG
+    }
+}
+
+// Runs from the physical addresses, before the MMU is on
 #[no_mangle]
 unsafe extern "C" fn set_mmu() {
     asm!(
@@ -24,49 +96,108 @@ unsafe extern "C" fn set_mmu() {
         in(reg) MAIR_CONFIG_DEFAULT,
     );
 
+    for table in [
+        L0_ADDR,
+        L1_ADDR,
+        L2_ADDR,
+        DEVICE_L1_ADDR,
+        DEVICE_L2_ADDR,
+        IDLE_L0_ADDR,
+    ] {
+        core::ptr::write_bytes(table as *mut Descriptor, 0, 512);
+    }
+
     // Set up PGD
     // 0b0000_0000_AAAA_AAAA_ABBB_BBBB_BBCC_CCCC_CCCD_DDDD_DDDD_XXXX_XXXX_XXXX
-    //             0000_0000_0
-    *(L0_ADDR as *mut u64) = L1_ADDR | PD_TABLE as u64;
+    //             0000_0000_0 is the linear map, 0000_0000_1 the devices
+    let l0 = L0_ADDR as *mut Descriptor;
+    *l0 = Descriptor::table(L1_ADDR);
+    *l0.add(1) = Descriptor::table(DEVICE_L1_ADDR);
 
     // Set up PUD
     // 0b0000_0000_AAAA_AAAA_ABBB_BBBB_BBCC_CCCC_CCCD_DDDD_DDDD_XXXX_XXXX_XXXX
     //                        000_0000_00
-    *(L1_ADDR as *mut u64) = L2_ADDR | PD_TABLE as u64;
+    let (l1, device_l1) = (
+        L1_ADDR as *mut Descriptor,
+        DEVICE_L1_ADDR as *mut Descriptor,
+    );
+    *l1 = Descriptor::table(L2_ADDR);
+    *device_l1 = Descriptor::table(DEVICE_L2_ADDR);
 
     // Set up PMD
     // 0b0000_0000_AAAA_AAAA_ABBB_BBBB_BBCC_CCCC_CCCD_DDDD_DDDD_XXXX_XXXX_XXXX
     //                                   00_0000_000
-    for i in (0x0000_0000 / (1 << 9) / (1 << 12))..(0x3C00_0000 / (1 << 9) / (1 << 12)) {
-        let addr = L2_ADDR + i * 8;
-        let attr: u64 = PD_ACCESS as u64 | (MAIR_NORMAL_NC_IDX as u64) << 2 | PD_BLOCK as u64;
-        *(addr as *mut u64) = attr | (i * (1 << 9) * (1 << 12));
+    // The blocks up to the end of .rodata are split into pages, the rest of
+    // the RAM is data. The guard page below the kernel stack stays unmapped.
+    let rodata_end = image_phys(&__rodata_end);
+    let guard = image_phys(&_start) - KERNEL_STACK_SIZE - GUARD_SIZE;
+    for i in 0..LINEAR_MAP_END / BLOCK_SIZE {
+        let entry = (L2_ADDR as *mut Descriptor).add(i as usize);
+        if i < KERNEL_L3_COUNT && i * BLOCK_SIZE < rodata_end {
+            let l3 = KERNEL_L3_ADDR + i * 0x1000;
+            for j in 0..512 {
+                let pa = i * BLOCK_SIZE + j * 0x1000;
+                *(l3 as *mut Descriptor).add(j as usize) =
+                    if (guard..guard + GUARD_SIZE).contains(&pa) {
+                        Descriptor::INVALID
+                    } else {
+                        Descriptor::page(pa, kernel_page_config(pa))
+                    };
+            }
+            *entry = Descriptor::table(l3);
+        } else {
+            *entry = Descriptor::block(i * BLOCK_SIZE, KERNEL_DATA_CONFIG);
+        }
     }
-    for i in (0x3C00_0000 / (1 << 9) / (1 << 12))..(0x4000_0000 / (1 << 9) / (1 << 12)) {
-        let addr = L2_ADDR + i * 8;
-        let attr: u64 = PD_ACCESS as u64 | (MAIR_DEVICE_NG_NR_NE_IDX as u64) << 2 | PD_BLOCK as u64;
-        *(addr as *mut u64) = attr | i * (1 << 9) * (1 << 12);
+    for i in LINEAR_MAP_END / BLOCK_SIZE..0x4000_0000 / BLOCK_SIZE {
+        let entry = (DEVICE_L2_ADDR as *mut Descriptor).add(i as usize);
+        *entry = Descriptor::block(i * BLOCK_SIZE, KERNEL_DEVICE_CONFIG);
     }
 
-    *(L1_ADDR.wrapping_add(8) as *mut u64) = 0x4000_0000 as u64
-        | PD_ACCESS as u64
-        | (MAIR_DEVICE_NG_NR_NE_IDX as u64) << 2
-        | PD_BLOCK as u64;
+    // The local peripherals from 0x4000_0000
+    *device_l1.add(1) = Descriptor::block(0x4000_0000, KERNEL_DEVICE_CONFIG);
+
+    // Identity map the RAM, the code enabling the MMU runs from there
+    *(IDLE_L0_ADDR as *mut Descriptor) = Descriptor::table(L1_ADDR);
 
     asm!(
-        "msr ttbr0_el1, {l0}",
+        "dsb ish",
+        "msr ttbr0_el1, {idle}",
         "msr ttbr1_el1, {l0}",
+        "tlbi vmalle1",
+        "ic iallu",
+        "dsb ish",
         "isb",
+        idle = in(reg) IDLE_L0_ADDR,
         l0 = in(reg) L0_ADDR,
     );
 
     asm!(
         "mrs {0}, sctlr_el1",
-        "orr {0}, {0}, #1",
+        "orr {0}, {0}, {1}",
         "msr sctlr_el1, {0}",
         "isb",
         out(reg) _,
+        in(reg) SCTLR_MMU_CACHES,
     )
     // 0x00c50838
     // 0b0000_0000_1100_0101_0000_1000_0011_1000
 }
+
+// Make code written through the data cache visible to instruction fetches
+pub fn sync_icache(addr: *const u8, len: usize) {
+    const LINE: usize = 64;
+    let start = addr as usize & !(LINE - 1);
+    for line in (start..addr as usize + len).step_by(LINE) {
+        unsafe { asm!("dc cvau, {}", in(reg) line) }
+    }
+    unsafe { asm!("dsb ish", "ic ialluis", "dsb ish", "isb") }
+}
+
+// Called once running from the high half, nothing below it is reachable
+// from the kernel afterwards
+#[no_mangle]
+unsafe extern "C" fn drop_identity_map() {
+    *(phys_to_virt(IDLE_L0_ADDR) as *mut Descriptor) = Descriptor::INVALID;
+    asm!("dsb ishst", "tlbi vmalle1is", "dsb ish", "isb");
+}
@@ -0,0 +1,98 @@
+// ASIDs tag the TLB entries of user mappings, so switching between processes
+// needs no flush. Every address space takes the next free ASID the first time
+// it runs. When they run out, a new generation starts: the TLB is flushed
+// once and the address spaces of the old generation get new ASIDs as they
+// are switched to.
+
+use super::config::IDLE_L0_ADDR;
+use core::arch::asm;
+use stdio::debug;
+
+// TCR_EL1.AS is clear, TTBR0 holds an 8-bit ASID
+const ASID_COUNT: u64 = 1 << 8;
+const ASID_SHIFT: u64 = 48;
+
+// ASID 0 belongs to the idle table, which maps nothing
+static mut NEXT: u64 = 1;
+// Generation 0 is never current, so new address spaces start out stale
+static mut GENERATION: u64 = 1;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Asid {
+    generation: u64,
+    id: u64,
+}
+
+impl Asid {
+    pub const fn new() -> Self {
+        Asid {
+            generation: 0,
+            id: 0,
+        }
+    }
+
+    fn is_current(&self) -> bool {
+        self.generation == unsafe { GENERATION }
+    }
+
+    // The TTBR0 value for the table at l0, taking a new ASID if this one is
+    // from an old generation
+    pub fn ttbr(&mut self, l0: u64) -> u64 {
+        if !self.is_current() {
+            unsafe {
+                if NEXT == ASID_COUNT {
+                    rollover();
+                }
+                self.generation = GENERATION;
+                self.id = NEXT;
+                NEXT += 1;
+            }
+        }
+        l0 | self.id << ASID_SHIFT
+    }
+
+    // Drop the translation of the page at va, entries of an old generation
+    // are gone already
+    pub fn flush_page(&self, va: u64) {
+        if self.is_current() {
+            let arg = self.id << ASID_SHIFT | (va >> 12) & 0xfff_ffff_ffff;
+            unsafe { asm!("dsb ishst", "tlbi vae1is, {}", "dsb ish", "isb", in(reg) arg) }
+        }
+    }
+
+    // Drop every translation tagged with this ASID
+    pub fn flush_all(&self) {
+        if self.is_current() {
+            let arg = self.id << ASID_SHIFT;
+            unsafe { asm!("dsb ishst", "tlbi aside1is, {}", "dsb ish", "isb", in(reg) arg) }
+        }
+    }
+
+    // Before the tables at l0 are freed: move TTBR0 off them if they are
+    // still in use, and drop what the TLB cached from them
+    pub fn retire(&self, l0: u64) {
+        let ttbr: u64;
+        unsafe { asm!("mrs {}, ttbr0_el1", out(reg) ttbr) }
+        if ttbr & ((1 << ASID_SHIFT) - 1) == l0 {
+            unsafe { asm!("msr ttbr0_el1, {}", "isb", in(reg) IDLE_L0_ADDR) }
+        }
+        self.flush_all();
+    }
+}
+
+// Start a new generation. TTBR0 moves to the idle table first, so that no
+// walk refills the TLB with the old ASID after the flush.
+unsafe fn rollover() {
+    GENERATION += 1;
+    NEXT = 1;
+    asm!(
+        "msr ttbr0_el1, {}",
+        "isb",
+        "dsb ishst",
+        "tlbi vmalle1is",
+        "dsb ish",
+        "isb",
+        in(reg) IDLE_L0_ADDR,
+    );
+    debug!("ASID rollover, generation {}", GENERATION);
+}
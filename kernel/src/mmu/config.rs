@@ -1,36 +1,83 @@
+use super::descriptor::Flags;
+
 // pub const PAGE_SIZE: usize = 4096;
 pub const ENTRY_COUNT: usize = 512;
 
 pub const TCR_CONFIG_REGION_48BIT: u64 = (64 - 48) << 0 | (64 - 48) << 16;
-pub const TCR_CONFIG_REGION_4KB: u64 = 0b00 << 14 | 0b00 << 30;
+// TG0 and TG1 encode the 4KB granule differently
+pub const TCR_CONFIG_REGION_4KB: u64 = 0b00 << 14 | 0b10 << 30;
+// Table walks are inner shareable and write-back cacheable, as the tables are
+pub const TCR_CONFIG_WALK_WB: u64 =
+    0b01 << 8 | 0b01 << 10 | 0b11 << 12 | 0b01 << 24 | 0b01 << 26 | 0b11 << 28;
 pub const TCR_CONFIG_DEFAULT: u64 =
-    TCR_CONFIG_REGION_48BIT | TCR_CONFIG_REGION_4KB | 0b101u64 << 32;
+    TCR_CONFIG_REGION_48BIT | TCR_CONFIG_REGION_4KB | TCR_CONFIG_WALK_WB | 0b101u64 << 32;
+
+// The MMU with the data and instruction caches
+pub const SCTLR_MMU_CACHES: u64 = 1 << 0 | 1 << 2 | 1 << 12;
 
 pub const MAIR_DEVICE_NG_NR_NE: u8 = 0b00000000;
-pub const MAIR_NORMAL_NC: u8 = 0b01000100;
+// Inner and outer write-back, read and write allocate
+pub const MAIR_NORMAL_WB: u8 = 0b11111111;
 pub const MAIR_DEVICE_NG_NR_NE_IDX: u8 = 0;
-pub const MAIR_NORMAL_NC_IDX: u8 = 1;
+pub const MAIR_NORMAL_WB_IDX: u8 = 1;
 pub const MAIR_CONFIG_DEFAULT: u64 = (MAIR_DEVICE_NG_NR_NE as u64)
     << (MAIR_DEVICE_NG_NR_NE_IDX * 8)
-    | (MAIR_NORMAL_NC as u64) << (MAIR_NORMAL_NC_IDX * 8);
+    | (MAIR_NORMAL_WB as u64) << (MAIR_NORMAL_WB_IDX * 8);
+
+// The kernel image is linked at this offset above its physical address, the
+// linear map of the RAM below LINEAR_MAP_END starts here as well
+pub const KERNEL_BASE: u64 = 0xffff_0000_0000_0000;
+pub const LINEAR_MAP_END: u64 = 0x3C00_0000;
+
+// The peripherals, from LINEAR_MAP_END to DEVICE_END, are mapped at this
+// offset above their physical address, away from the linear map
+pub const DEVICE_BASE: u64 = 0xffff_0080_0000_0000;
+pub const DEVICE_END: u64 = 0x8000_0000;
 
+// The boot stack right below the kernel image, which the exception handlers
+// run on as well, with an unmapped guard page under it
+pub const KERNEL_STACK_SIZE: u64 = 0x2_0000;
+pub const GUARD_SIZE: u64 = 0x1000;
+
+// Kernel tables walked from TTBR1, one tree for each of the two mappings
 pub const L0_ADDR: u64 = 0x1000;
 pub const L1_ADDR: u64 = 0x2000;
 pub const L2_ADDR: u64 = 0x3000;
+pub const DEVICE_L1_ADDR: u64 = 0x4000;
+pub const DEVICE_L2_ADDR: u64 = 0x5000;
+// Page granular tables for the 2MB blocks holding the kernel image, so each
+// of its sections gets its own permissions
+pub const KERNEL_L3_ADDR: u64 = 0x7000;
+pub const KERNEL_L3_COUNT: u64 = 8;
+
+// TTBR0 table while no process runs. It identity maps the RAM only until the
+// kernel has jumped to the high half, and stays empty afterwards.
+pub const IDLE_L0_ADDR: u64 = 0x6000;
+
+// Kernel mappings. Only .text is executable, and never from EL0.
+pub const KERNEL_TEXT_CONFIG: Flags = Flags::ACCESSED
+    .union(Flags::READ_ONLY)
+    .union(Flags::NORMAL)
+    .union(Flags::INNER_SHAREABLE)
+    .union(Flags::UXN);
 
-pub const PD_TABLE: u32 = 0b11;
-pub const PD_BLOCK: u32 = 0b01;
-pub const PD_PAGE: u32 = 0b11;
-pub const PD_ACCESS: u32 = 1 << 10;
+pub const KERNEL_RODATA_CONFIG: Flags = KERNEL_TEXT_CONFIG.union(Flags::PXN);
 
-pub const AP_RW_EL0: usize = 0b01 << 6;
-pub const AP_RO_EL0: usize = 0b11 << 6;
+pub const KERNEL_DATA_CONFIG: Flags = Flags::ACCESSED
+    .union(Flags::NORMAL)
+    .union(Flags::INNER_SHAREABLE)
+    .union(Flags::PXN)
+    .union(Flags::UXN);
 
-pub const STACK_CONFIG: u32 =
-    PD_ACCESS | AP_RW_EL0 as u32 | (MAIR_NORMAL_NC_IDX as u32) << 2 as u32 | PD_PAGE as u32;
+pub const KERNEL_DEVICE_CONFIG: Flags = Flags::ACCESSED
+    .union(Flags::DEVICE)
+    .union(Flags::PXN)
+    .union(Flags::UXN);
 
-pub const TEXT_CONFIG: u32 =
-    PD_ACCESS | AP_RO_EL0 as u32 | (MAIR_NORMAL_NC_IDX as u32) << 2 as u32 | PD_PAGE as u32;
+// User mappings, tagged with the ASID and never executable from EL1
+pub const USER_CONFIG: Flags = Flags::ACCESSED
+    .union(Flags::USER)
+    .union(Flags::NOT_GLOBAL)
+    .union(Flags::PXN);
 
-pub const GPU_CONFIG: u32 =
-    PD_ACCESS | AP_RW_EL0 as u32 | (MAIR_DEVICE_NG_NR_NE_IDX as u32) << 2 as u32 | PD_PAGE as u32;
+pub const GPU_CONFIG: Flags = USER_CONFIG.union(Flags::DEVICE).union(Flags::UXN);
@@ -0,0 +1,118 @@
+use super::config::{MAIR_DEVICE_NG_NR_NE_IDX, MAIR_NORMAL_WB_IDX};
+use core::fmt::Debug;
+use core::ops::{BitOr, BitOrAssign};
+
+// Bits 47:12, the output address or the next table
+const ADDR_MASK: u64 = 0xffff_ffff_f000;
+// Bits 1:0, invalid when bit 0 is clear
+const VALID: u64 = 0b01;
+const TABLE: u64 = 0b11;
+
+// Attribute bits of block and page descriptors
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Flags(u64);
+
+impl Flags {
+    // AttrIndx, which MAIR_EL1 attribute the memory has
+    pub const DEVICE: Flags = Flags((MAIR_DEVICE_NG_NR_NE_IDX as u64) << 2);
+    pub const NORMAL: Flags = Flags((MAIR_NORMAL_WB_IDX as u64) << 2);
+    // AP[1], EL0 gets the same access as EL1
+    pub const USER: Flags = Flags(1 << 6);
+    // AP[2], no writes from any level
+    pub const READ_ONLY: Flags = Flags(1 << 7);
+    pub const INNER_SHAREABLE: Flags = Flags(0b11 << 8);
+    // AF, the first access faults without it
+    pub const ACCESSED: Flags = Flags(1 << 10);
+    // nG, the TLB entry is tagged with the ASID
+    pub const NOT_GLOBAL: Flags = Flags(1 << 11);
+    pub const PXN: Flags = Flags(1 << 53);
+    pub const UXN: Flags = Flags(1 << 54);
+
+    // Everything a descriptor holds besides its type and address
+    const MASK: u64 = 0xffc | 0b111 << 52;
+
+    pub const fn union(self, other: Flags) -> Self {
+        Flags(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, other: Flags) -> Flags {
+        self.union(other)
+    }
+}
+
+impl BitOrAssign for Flags {
+    fn bitor_assign(&mut self, other: Flags) {
+        *self = self.union(other);
+    }
+}
+
+impl Debug for Flags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Flags(0x{:x})", self.0)
+    }
+}
+
+// One entry of a translation table, as the MMU reads it. Whether a valid
+// descriptor is a table or a block depends on the level it is found at.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct Descriptor(u64);
+
+impl Descriptor {
+    pub const INVALID: Descriptor = Descriptor(0);
+
+    // The next level table at the physical address pa
+    pub const fn table(pa: u64) -> Self {
+        Descriptor(pa & ADDR_MASK | TABLE)
+    }
+
+    // A 1GB block at level 1 or a 2MB block at level 2
+    pub const fn block(pa: u64, flags: Flags) -> Self {
+        Descriptor(pa & ADDR_MASK | flags.0 | VALID)
+    }
+
+    // A 4KB page, only found at level 3
+    pub const fn page(pa: u64, flags: Flags) -> Self {
+        Descriptor(pa & ADDR_MASK | flags.0 | TABLE)
+    }
+
+    pub fn is_valid(self) -> bool {
+        self.0 & VALID != 0
+    }
+
+    pub fn is_table(self, level: usize) -> bool {
+        level < 3 && self.0 & TABLE == TABLE
+    }
+
+    // A block or a page, which ends the walk
+    pub fn is_leaf(self, level: usize) -> bool {
+        self.is_valid() && !self.is_table(level)
+    }
+
+    pub fn addr(self) -> u64 {
+        self.0 & ADDR_MASK
+    }
+
+    pub fn flags(self) -> Flags {
+        Flags(self.0 & Flags::MASK)
+    }
+
+    // The same block or page with other attributes
+    pub fn with_flags(self, flags: Flags) -> Self {
+        Descriptor(self.0 & !Flags::MASK | flags.0)
+    }
+}
+
+impl Debug for Descriptor {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Descriptor(0x{:016x})", self.0)
+    }
+}
@@ -1,169 +1,260 @@
 use super::config::ENTRY_COUNT;
-use super::entry::Entry;
-use alloc::alloc::alloc;
-use alloc::boxed::Box;
-use alloc::vec;
-use alloc::vec::Vec;
+use super::descriptor::{Descriptor, Flags};
+use super::{phys_to_virt, virt_to_phys};
+use alloc::alloc::{alloc, dealloc};
 use core::alloc::Layout;
+use core::arch::asm;
+use stdio::println;
 
-use crate::mmu::config::MAIR_NORMAL_NC_IDX;
-use crate::mmu::config::PD_ACCESS;
-use crate::mmu::config::PD_PAGE;
-use crate::mmu::config::PD_TABLE;
+const PAGE_SIZE: u64 = 0x1000;
 
-#[derive(Debug)]
-pub struct PageTable {
-    entries: Vec<Entry>,
-    pub addr: u64,
+// Pages holding page tables, a table goes back once it maps nothing
+static mut TABLE_PAGES: usize = 0;
+
+pub fn table_pages() -> usize {
+    unsafe { TABLE_PAGES }
 }
 
-impl PageTable {
-    pub fn new() -> Self {
-        let addr = unsafe { alloc(Layout::from_size_align(0x1000, 0x1000).unwrap()) as u64 };
-        unsafe {
-            let mut p = addr as *mut u8;
-            for _ in 0..0x1000 {
-                *p = 0;
-                p = p.add(1);
-            }
-        }
-        PageTable {
-            entries: vec![Entry::new(); ENTRY_COUNT],
-            addr,
-        }
+// Bytes one descriptor maps at a level, 512GB down to 4KB
+const fn entry_size(level: usize) -> u64 {
+    1 << (12 + 9 * (3 - level))
+}
+
+fn index(va: u64, level: usize) -> usize {
+    ((va >> (12 + 9 * (3 - level))) & 0x1ff) as usize
+}
+
+fn table_layout() -> Layout {
+    Layout::from_size_align(0x1000, 0x1000).unwrap()
+}
+
+// The descriptors of the table at the physical address pa
+unsafe fn entries<'a>(pa: u64) -> &'a mut [Descriptor; ENTRY_COUNT] {
+    &mut *(phys_to_virt(pa) as *mut [Descriptor; ENTRY_COUNT])
+}
+
+// A zeroed table, by its physical address. None when out of memory.
+fn alloc_table() -> Option<u64> {
+    let page = unsafe { alloc(table_layout()) };
+    if page.is_null() {
+        return None;
     }
+    unsafe {
+        core::ptr::write_bytes(page, 0, 0x1000);
+        TABLE_PAGES += 1;
+    }
+    Some(virt_to_phys(page as u64))
+}
 
-    pub fn load(addr: u64, level: u64) -> Self {
-        // println!("PageTable::load: 0x{:x}", addr);
-        let mut entries = vec![Entry::new(); ENTRY_COUNT];
-        for i in 0..ENTRY_COUNT {
-            let entry_addr = addr + (i * 8) as u64;
-            let entry = unsafe { *(entry_addr as *const u64) };
-            if entry & 0b1 == 1 {
-                if level < 3 {
-                    let pt = Box::new(PageTable::load(entry & 0xffff_ffff_ffff_f000, level + 1));
-                    entries[i] = Entry::PdTable(pt);
-                } else {
-                    entries[i] = Entry::PdBlock((entry_addr as *mut u64, entry));
-                }
-            }
+// Free a table with the tables below it, the memory they map is not touched
+unsafe fn free_table(pa: u64, level: usize) {
+    for entry in entries(pa).iter() {
+        if entry.is_table(level) {
+            free_table(entry.addr(), level + 1);
         }
-        PageTable { entries, addr }
     }
+    dealloc(phys_to_virt(pa) as *mut u8, table_layout());
+    TABLE_PAGES -= 1;
+}
 
-    pub fn get_entry(&self, idx: usize) -> &Entry {
-        &self.entries[idx]
+fn leaf(pa: u64, flags: Flags, level: usize) -> Descriptor {
+    if level == 3 {
+        Descriptor::page(pa, flags)
+    } else {
+        Descriptor::block(pa, flags)
     }
+}
 
-    pub fn get_page(&self, addr: u64, level: u64) -> &Entry {
-        let idx = ((addr >> (12 + 9 * (3 - level))) & 0x1ff) as usize;
-        if level == 3 {
-            return self.get_entry(idx);
-        }
-        if !self.exists(idx) {
-            return &Entry::None;
-        }
-        if let Entry::PdTable(pt) = self.get_entry(idx) {
-            return pt.get_page(addr, level + 1);
+// The table below an entry, created when the entry is invalid. None when it
+// is a live block or page, changing those in place without break-before-make
+// could leave the TLB with conflicting entries.
+unsafe fn next_table(entry: &mut Descriptor, level: usize) -> Option<u64> {
+    if !entry.is_valid() {
+        *entry = Descriptor::table(alloc_table()?);
+    } else if !entry.is_table(level) {
+        return None;
+    }
+    Some(entry.addr())
+}
+
+// Where the entry covering va ends, clipped to end
+fn entry_end(va: u64, end: u64, level: usize) -> u64 {
+    let size = entry_size(level);
+    ((va & !(size - 1)) + size).min(end)
+}
+
+unsafe fn map_range(
+    table: u64,
+    level: usize,
+    va: u64,
+    end: u64,
+    pa: u64,
+    flags: Flags,
+) -> Option<()> {
+    let (mut va, mut pa) = (va, pa);
+    while va < end {
+        let next = entry_end(va, end, level);
+        let entry = &mut entries(table)[index(va, level)];
+        // Level 0 has no blocks, and a table already there keeps the
+        // mappings around the range
+        let whole = level > 0 && next - va == entry_size(level) && pa % entry_size(level) == 0;
+        if whole && !entry.is_valid() {
+            *entry = leaf(pa, flags, level);
+        } else {
+            map_range(next_table(entry, level)?, level + 1, va, next, pa, flags)?;
         }
-        &Entry::None
+        pa += next - va;
+        va = next;
     }
+    Some(())
+}
 
-    pub fn create_page(&mut self, addr: u64, level: u64) -> &mut Entry {
-        let idx = ((addr >> (12 + 9 * (3 - level))) & 0x1ff) as usize;
-        if level == 3 {
-            if !self.exists(idx as usize) {
-                self.set_entry(
-                    idx as usize,
-                    Entry::PdBlock(((self.addr + (idx * 8) as u64) as *mut u64, 0)),
-                );
+// Returns whether a table was freed
+unsafe fn unmap_range(table: u64, level: usize, va: u64, end: u64) -> bool {
+    let mut va = va;
+    let mut freed = false;
+    while va < end {
+        let next = entry_end(va, end, level);
+        let entry = &mut entries(table)[index(va, level)];
+        if entry.is_table(level) && next - va < entry_size(level) {
+            let below = entry.addr();
+            freed |= unmap_range(below, level + 1, va, next);
+            if entries(below).iter().all(|entry| !entry.is_valid()) {
+                *entry = Descriptor::INVALID;
+                free_table(below, level + 1);
+                freed = true;
             }
-            return self.get_entry_mut(idx as usize);
-        }
-        if !self.exists(idx as usize) {
-            let pg = PageTable::new();
-            self.set_entry(idx as usize, Entry::PdTable(Box::new(pg)));
-            if let Entry::PdTable(pt) = self.get_entry_mut(idx as usize) {
-                return pt.create_page(addr, level + 1);
+        } else if entry.is_valid() {
+            // Splitting a block would take memory, so one only partly in the
+            // range goes as a whole
+            debug_assert!(
+                next - va == entry_size(level),
+                "unmap: 0x{:x}-0x{:x} cuts through a block",
+                va,
+                next
+            );
+            let old = core::mem::replace(entry, Descriptor::INVALID);
+            if old.is_table(level) {
+                free_table(old.addr(), level + 1);
+                freed = true;
             }
-            panic!("get_page: not a PdTable");
         }
-        let entry = self.get_entry_mut(idx as usize);
-        if let Entry::PdTable(pt) = entry {
-            return pt.create_page(addr, level + 1);
-        }
-        panic!("get_page: not a PdTable");
+        va = next;
     }
+    freed
+}
 
-    pub fn get_entry_mut(&mut self, idx: usize) -> &mut Entry {
-        &mut self.entries[idx]
+unsafe fn protect_range(table: u64, level: usize, va: u64, end: u64, flags: Flags) -> Option<()> {
+    let mut va = va;
+    while va < end {
+        let next = entry_end(va, end, level);
+        let entry = &mut entries(table)[index(va, level)];
+        if entry.is_leaf(level) && next - va == entry_size(level) {
+            *entry = entry.with_flags(flags);
+        } else if entry.is_valid() {
+            protect_range(next_table(entry, level)?, level + 1, va, next, flags)?;
+        }
+        va = next;
     }
+    Some(())
+}
 
-    pub fn set_entry(&mut self, idx: usize, entry: Entry) {
-        assert!(idx < ENTRY_COUNT);
-        assert!(self.entries[idx].is_valid() == false);
-        self.entries[idx] = entry;
-        match &self.entries[idx] {
-            Entry::PdTable(pt) => {
-                let entry_addr = self.addr + (idx * 8) as u64;
-                unsafe {
-                    let entry = entry_addr as *mut u64;
-                    *entry =
-                        pt.addr | (PD_TABLE | PD_ACCESS | (MAIR_NORMAL_NC_IDX << 2) as u32) as u64;
-                }
-            }
-            Entry::PdBlock((saddr, addr)) => {
-                assert!(*addr == 0);
-                unsafe {
-                    let entry = *saddr as *mut u64;
-                    *entry = addr | (PD_PAGE | PD_ACCESS | (MAIR_NORMAL_NC_IDX << 2) as u32) as u64;
-                }
-            }
-            _ => {}
+// The physical address va translates to through the tables at root, with
+// the attributes of the mapping
+pub fn translate(root: u64, va: u64) -> Option<(u64, Flags)> {
+    let mut table = root;
+    for level in 0..4 {
+        let entry = unsafe { entries(table)[index(va, level)] };
+        if !entry.is_valid() {
+            return None;
         }
+        if !entry.is_table(level) {
+            return Some((entry.addr() | va & (entry_size(level) - 1), entry.flags()));
+        }
+        table = entry.addr();
     }
+    unreachable!()
+}
 
-    pub fn exists(&self, idx: usize) -> bool {
-        self.entries[idx].is_valid()
+// Complete the table writes before the walker can see them
+fn sync() {
+    unsafe { asm!("dsb ishst", "isb") }
+}
+
+// The translation tables of a user address space. They are freed along with
+// it, the memory they map is not.
+#[derive(Debug)]
+pub struct PageTable {
+    // Physical address of the level 0 table
+    root: u64,
+}
+
+impl PageTable {
+    // None when out of memory
+    pub fn new() -> Option<Self> {
+        Some(PageTable {
+            root: alloc_table()?,
+        })
+    }
+
+    // The address TTBR0 refers to the table by
+    pub fn phys(&self) -> u64 {
+        self.root
+    }
+
+    // Map len bytes at va to the physical memory from pa, with 2MB and 1GB
+    // blocks wherever both addresses are aligned for them. Nothing in the
+    // range may be mapped yet. None when out of memory for a table or when
+    // something is, what was mapped until then stays mapped.
+    pub fn map(&mut self, va: u64, pa: u64, len: u64, flags: Flags) -> Option<()> {
+        assert!((va | pa | len) % PAGE_SIZE == 0);
+        let ret = unsafe { map_range(self.root, 0, va, va + len, pa, flags) };
+        sync();
+        ret
+    }
+
+    // Unmap len bytes at va and free the tables left empty. Nothing is
+    // allocated, so the range must not cut through a block. Returns whether a
+    // table was freed, then walk cache entries for addresses outside the
+    // range may be stale as well.
+    pub fn unmap(&mut self, va: u64, len: u64) -> bool {
+        assert!((va | len) % PAGE_SIZE == 0);
+        let ret = unsafe { unmap_range(self.root, 0, va, va + len) };
+        sync();
+        ret
+    }
+
+    // Change the attributes of whatever is mapped in len bytes at va, holes
+    // are skipped. None when the range cuts through a block, which is not
+    // split.
+    pub fn protect(&mut self, va: u64, len: u64, flags: Flags) -> Option<()> {
+        assert!((va | len) % PAGE_SIZE == 0);
+        let ret = unsafe { protect_range(self.root, 0, va, va + len, flags) };
+        sync();
+        ret
+    }
+
+    #[allow(dead_code)]
+    pub fn dump(&self) {
+        println!("PageTable: 0x{:x}", self.root);
+        unsafe { dump(self.root, 0) }
     }
 }
 
-impl Clone for PageTable {
-    fn clone(&self) -> Self {
-        let addr = unsafe { alloc(Layout::from_size_align(0x1000, 0x1000).unwrap()) as u64 };
-        unsafe {
-            let mut p = addr as *mut u8;
-            for _ in 0..0x1000 {
-                *p = 0;
-                p = p.add(1);
-            }
-        };
-        let entries = self.entries.clone();
-        for i in 0..ENTRY_COUNT {
-            if self.entries[i].is_valid() {
-                let entry = self.entries[i].clone();
-                match entry {
-                    Entry::PdBlock((saddr, pg)) => {
-                        assert!(saddr as u64 == 0);
-                        let entry_addr = addr + (i * 8) as u64;
-                        unsafe {
-                            let entry = entry_addr as *mut u64;
-                            *entry = pg;
-                        }
-                    }
-                    Entry::PdTable(pt) => {
-                        let pt = pt.clone();
-                        let entry_addr = addr + (i * 8) as u64;
-                        unsafe {
-                            let entry = entry_addr as *mut u64;
-                            *entry = pt.addr
-                                | (PD_TABLE | PD_ACCESS | (MAIR_NORMAL_NC_IDX << 2) as u32) as u64;
-                        }
-                    }
-                    _ => {}
-                }
+impl Drop for PageTable {
+    fn drop(&mut self) {
+        unsafe { free_table(self.root, 0) }
+    }
+}
+
+#[allow(dead_code)]
+unsafe fn dump(table: u64, level: usize) {
+    for (i, entry) in entries(table).iter().enumerate() {
+        if entry.is_valid() {
+            println!("{:1$}[{2:03}] = {3:?}", "", 2 + level * 2, i, entry);
+            if entry.is_table(level) {
+                dump(entry.addr(), level + 1);
             }
         }
-        PageTable { entries, addr }
     }
 }
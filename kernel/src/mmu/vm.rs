@@ -1,108 +1,506 @@
-use super::entry::Entry;
-use super::page_table::PageTable;
-use alloc::alloc::alloc;
+use super::asid::Asid;
+use super::config::LINEAR_MAP_END;
+use super::descriptor::Flags;
+use super::page_table::{self, PageTable};
+use super::{phys_to_virt, sync_icache, virt_to_phys};
+use alloc::alloc::{alloc, dealloc};
+use alloc::rc::Rc;
+use alloc::vec::Vec;
 use core::alloc::Layout;
-use stdio::println;
+use core::mem::{size_of, MaybeUninit};
+use stdio::{debug, println, trace};
+
+pub use abi::mman::{MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MAP_SHARED};
+pub use abi::mman::{PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
+
+const PAGE_SIZE: u64 = 0x1000;
+
+// Kernel chosen mappings are placed from here upwards
+const MMAP_BASE: u64 = 0x1000_0000_0000;
+const MMAP_END: u64 = 0xffff_0000_0000;
+// TTBR0 translates the lower 48 bits of the address space
+const USER_END: u64 = 1 << 48;
+
+fn page_align_up(addr: u64) -> u64 {
+    (addr + PAGE_SIZE - 1) & !(PAGE_SIZE - 1)
+}
+
+// The page aligned end of len bytes at addr, None past the user addresses
+fn range_end(addr: u64, len: usize) -> Option<u64> {
+    let len = (len as u64).checked_add(PAGE_SIZE - 1)? & !(PAGE_SIZE - 1);
+    addr.checked_add(len).filter(|&end| end <= USER_END)
+}
+
+// Translate PROT_* bits into the attributes of a user page
+fn prot_to_flag(prot: u64) -> Flags {
+    let mut flag =
+        Flags::ACCESSED | Flags::NOT_GLOBAL | Flags::PXN | Flags::NORMAL | Flags::INNER_SHAREABLE;
+    // Without USER, EL0 has no access at all
+    if prot & (PROT_READ | PROT_WRITE | PROT_EXEC) != PROT_NONE {
+        flag |= Flags::USER;
+        if prot & PROT_WRITE == 0 {
+            flag |= Flags::READ_ONLY;
+        }
+    }
+    if prot & PROT_EXEC == 0 {
+        flag |= Flags::UXN;
+    }
+    flag
+}
+
+// Where the kernel reaches the user address addr through the tables at
+// root, in the linear map. None unless EL0 may read it, and write it as well
+// if write is set, and it is RAM rather than a device.
+fn user_ptr(root: u64, addr: u64, write: bool) -> Option<*mut u8> {
+    if addr >= USER_END {
+        return None;
+    }
+    let (pa, flags) = page_table::translate(root, addr)?;
+    if !flags.contains(Flags::USER)
+        || write && flags.contains(Flags::READ_ONLY)
+        || pa >= LINEAR_MAP_END
+    {
+        return None;
+    }
+    Some(phys_to_virt(pa) as *mut u8)
+}
+
+// A zeroed physical page owned by one or more mappings
+#[derive(Debug)]
+struct Frame(*mut u8);
+
+impl Frame {
+    // None when out of memory
+    fn new() -> Option<Self> {
+        let page = unsafe { alloc(Layout::from_size_align(0x1000, 0x1000).unwrap()) };
+        if page.is_null() {
+            return None;
+        }
+        unsafe {
+            core::ptr::write_bytes(page, 0, 0x1000);
+        }
+        Some(Frame(page))
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        unsafe {
+            dealloc(self.0, Layout::from_size_align(0x1000, 0x1000).unwrap());
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Area {
+    start: u64,
+    prot: u64,
+    shared: bool,
+    frames: Vec<Rc<Frame>>,
+}
+
+impl Area {
+    fn end(&self) -> u64 {
+        self.start + self.frames.len() as u64 * PAGE_SIZE
+    }
+}
 
 #[derive(Debug)]
 pub struct VirtualMemory {
     root: PageTable,
+    areas: Vec<Area>,
+    asid: Asid,
 }
 
 impl VirtualMemory {
-    pub fn new() -> Self {
-        VirtualMemory {
-            root: PageTable::new(),
+    // None when out of memory
+    pub fn new() -> Option<Self> {
+        Some(VirtualMemory {
+            root: PageTable::new()?,
+            areas: Vec::new(),
+            asid: Asid::new(),
+        })
+    }
+
+    // The physical address of the root table
+    pub fn get_l0_addr(&self) -> *mut u8 {
+        self.root.phys() as *mut u8
+    }
+
+    // The value for TTBR0 when switching to this address space
+    pub fn ttbr0(&mut self) -> u64 {
+        self.asid.ttbr(self.root.phys())
+    }
+
+    // None when out of memory for the tables
+    pub fn map_pa(&mut self, addr: u64, pa: u64, size: usize, flag: Flags) -> Option<()> {
+        debug!(
+            "map_pa: 0x{:x} -> 0x{:x}, size: 0x{:x}, flag: {:?}",
+            addr, pa, size, flag
+        );
+        self.root.map(addr, pa, size as u64, flag)
+    }
+
+    // None when out of memory or when a page is already mapped, the pages
+    // mapped until then are unmapped again and the others left alone
+    fn map_area(&mut self, area: &Area) -> Option<()> {
+        let flag = prot_to_flag(area.prot);
+        for (i, frame) in area.frames.iter().enumerate() {
+            let va = area.start + i as u64 * PAGE_SIZE;
+            let mapped = self
+                .root
+                .map(va, virt_to_phys(frame.0 as u64), PAGE_SIZE, flag);
+            if mapped.is_none() {
+                self.unmap_range(area.start, i as u64 * PAGE_SIZE);
+                return None;
+            }
+            // The frame was filled through the data cache
+            if area.prot & PROT_EXEC != 0 {
+                sync_icache(frame.0, PAGE_SIZE as usize);
+            }
         }
+        Some(())
     }
 
-    pub fn load(addr: u64) -> Self {
-        // println!("VirtualMemory::load: 0x{:x}", addr);
-        VirtualMemory {
-            root: PageTable::load(addr, 0),
+    // Unmap a range mapped by pages and drop it from the TLB, every
+    // translation of the ASID when tables were freed along
+    fn unmap_range(&mut self, start: u64, len: u64) {
+        if self.root.unmap(start, len) {
+            self.asid.flush_all();
+        } else {
+            for va in (start..start + len).step_by(PAGE_SIZE as usize) {
+                self.asid.flush_page(va);
+            }
         }
     }
 
-    pub fn get_l0_addr(&self) -> *mut u8 {
-        self.root.addr as *mut u8
+    fn is_free(&self, start: u64, end: u64) -> bool {
+        start >= MMAP_BASE
+            && end <= MMAP_END
+            && self
+                .areas
+                .iter()
+                .all(|area| area.end() <= start || end <= area.start)
     }
 
-    fn get_page(&self, addr: u64) -> &Entry {
-        self.root.get_page(addr, 0)
+    // First fit search for an unused range above MMAP_BASE
+    fn find_free(&self, len: u64) -> Option<u64> {
+        let mut start = MMAP_BASE;
+        let mut areas: Vec<&Area> = self.areas.iter().collect();
+        areas.sort_by_key(|area| area.start);
+        for area in areas {
+            if area.end() <= start {
+                continue;
+            }
+            if start + len <= area.start {
+                break;
+            }
+            start = area.end();
+        }
+        if start + len <= MMAP_END {
+            Some(start)
+        } else {
+            None
+        }
     }
 
-    fn create_page(&mut self, addr: u64) -> &mut Entry {
-        self.root.create_page(addr, 0)
+    // Split the area containing addr so that no area straddles it
+    fn split_at(&mut self, addr: u64) {
+        let pos = self
+            .areas
+            .iter()
+            .position(|area| area.start < addr && addr < area.end());
+        if let Some(pos) = pos {
+            let area = &mut self.areas[pos];
+            let idx = ((addr - area.start) / PAGE_SIZE) as usize;
+            let frames = area.frames.split_off(idx);
+            let upper = Area {
+                start: addr,
+                prot: area.prot,
+                shared: area.shared,
+                frames,
+            };
+            self.areas.push(upper);
+        }
     }
 
-    pub fn map_pa(&mut self, addr: u64, pa: u64, size: usize, flag: u32) -> *mut u8 {
-        println!(
-            "map_pa: 0x{:x} -> 0x{:x}, size: 0x{:x}, flag: 0x{:x}",
-            addr, pa, size, flag
-        );
-        let flag = flag & 0xfff;
-        for i in (0..size).step_by(0x1000) {
-            let page = self.create_page(addr + i as u64);
-            page.set_addr((pa + i as u64) as u32);
-            page.set_flag(flag);
+    // Map len bytes, optionally filled with data, returning the chosen address
+    pub fn mmap_area(
+        &mut self,
+        addr: u64,
+        len: usize,
+        prot: u64,
+        flags: u64,
+        data: Option<&[u8]>,
+    ) -> Option<u64> {
+        // Bounding len keeps the sums below from overflowing
+        if len == 0 || addr % PAGE_SIZE != 0 || len as u64 > MMAP_END - MMAP_BASE {
+            return None;
         }
-        println!("map_pa: 0x{:x} -> 0x{:x}", addr, pa);
-        return addr as *mut u8;
+        let len = page_align_up(len as u64);
+        let start = if flags & MAP_FIXED != 0 {
+            // The program, heap, stack and trampoline outside the mmap range
+            // are placed by the kernel alone
+            match addr.checked_add(len) {
+                Some(end) if addr >= MMAP_BASE && end <= MMAP_END => {}
+                _ => return None,
+            }
+            self.munmap(addr, len as usize);
+            addr
+        } else if addr != 0 && addr <= MMAP_END && self.is_free(addr, addr + len) {
+            addr
+        } else {
+            self.find_free(len)?
+        };
+        self.insert_area(start, len, prot, flags & MAP_SHARED != 0, data)?;
+        Some(start)
+    }
+
+    // Map a private copy of a program image, so that every process gets its own .data and .bss
+    pub fn map_image(&mut self, addr: u64, image: &[u8], prot: u64) -> Option<()> {
+        let len = page_align_up(image.len() as u64);
+        self.insert_area(addr, len, prot, false, Some(image))
     }
 
-    pub fn mmap(&mut self, addr: u64, size: usize, flag: u32) -> *mut u8 {
-        println!("mmap: 0x{:x}, size: 0x{:x}, flag: 0x{:x}", addr, size, flag);
-        let flag = flag & 0xfff;
-        let mem = unsafe { alloc(Layout::from_size_align(size, 0x1000).unwrap()) as *mut u8 };
-        for i in (0..size).step_by(0x1000) {
-            let page = self.create_page(addr + i as u64);
-            page.set_addr((mem as u64 + i as u64) as u32);
-            page.set_flag(flag);
+    // Map len zeroed bytes at an address the kernel picked, like the stack,
+    // None when out of memory
+    pub fn map_zeroed(&mut self, addr: u64, len: usize, prot: u64) -> Option<()> {
+        trace!("map_zeroed: 0x{:x}, len: 0x{:x}", addr, len);
+        self.insert_area(addr, page_align_up(len as u64), prot, false, None)
+    }
+
+    // None when out of memory, the frames taken so far are released
+    fn insert_area(
+        &mut self,
+        start: u64,
+        len: u64,
+        prot: u64,
+        shared: bool,
+        data: Option<&[u8]>,
+    ) -> Option<()> {
+        let mut frames = Vec::new();
+        for _ in 0..len / PAGE_SIZE {
+            frames.push(Rc::new(Frame::new()?));
         }
-        println!("mmap: 0x{:x} -> 0x{:x}", addr, mem as usize);
-        return addr as *mut u8;
+        if let Some(data) = data {
+            for (frame, chunk) in frames.iter().zip(data.chunks(PAGE_SIZE as usize)) {
+                unsafe {
+                    core::ptr::copy_nonoverlapping(chunk.as_ptr(), frame.0, chunk.len());
+                }
+            }
+        }
+        let area = Area {
+            start,
+            prot,
+            shared,
+            frames,
+        };
+        self.map_area(&area)?;
+        self.areas.push(area);
+        Some(())
     }
 
-    pub fn get_phys(&self, addr: u64) -> *mut u8 {
-        let page = self.get_page(addr);
-        (page.get_addr() as u64 | addr & 0xfff) as *mut u8
+    pub fn munmap(&mut self, addr: u64, len: usize) -> bool {
+        if addr % PAGE_SIZE != 0 || len == 0 {
+            return false;
+        }
+        let end = match range_end(addr, len) {
+            Some(end) => end,
+            None => return false,
+        };
+        self.split_at(addr);
+        self.split_at(end);
+        let (removed, kept) = self
+            .areas
+            .drain(..)
+            .partition(|area| addr <= area.start && area.end() <= end);
+        self.areas = kept;
+        for area in removed {
+            self.unmap_range(area.start, area.end() - area.start);
+        }
+        true
     }
 
-    #[allow(dead_code)]
-    pub fn dump(&self) {
-        println!("VirtualMemory:");
-        println!("  root: 0x{:x}", self.root.addr);
-        unsafe { dump(self.root.addr as *mut u64, 0) }
+    pub fn mprotect(&mut self, addr: u64, len: usize, prot: u64) -> bool {
+        if addr % PAGE_SIZE != 0 {
+            return false;
+        }
+        let end = match range_end(addr, len) {
+            Some(end) => end,
+            None => return false,
+        };
+        // Every page of the range has to be mapped
+        let mut cur = addr;
+        while cur < end {
+            match self.areas.iter().find(|a| a.start <= cur && cur < a.end()) {
+                Some(area) => cur = area.end(),
+                None => return false,
+            }
+        }
+        self.split_at(addr);
+        self.split_at(end);
+        for area in self.areas.iter_mut() {
+            if addr <= area.start && area.end() <= end {
+                area.prot = prot;
+                // Executable now, the frames may hold code written as data
+                if prot & PROT_EXEC != 0 {
+                    for frame in area.frames.iter() {
+                        sync_icache(frame.0, PAGE_SIZE as usize);
+                    }
+                }
+            }
+        }
+        // Areas are mapped by pages, so no block is split
+        let ret = self
+            .root
+            .protect(addr, end - addr, prot_to_flag(prot))
+            .is_some();
+        for va in (addr..end).step_by(PAGE_SIZE as usize) {
+            self.asid.flush_page(va);
+        }
+        ret
+    }
+
+    // Give the child of a fork its own copy of private areas and
+    // the very same frames for shared ones, None when out of memory
+    pub fn fork_areas(&self, child: &mut VirtualMemory) -> Option<()> {
+        for area in self.areas.iter() {
+            let area = if area.shared {
+                area.clone()
+            } else {
+                let frames = area
+                    .frames
+                    .iter()
+                    .map(|frame| {
+                        let copy = Frame::new()?;
+                        unsafe {
+                            core::ptr::copy_nonoverlapping(frame.0, copy.0, 0x1000);
+                        }
+                        Some(Rc::new(copy))
+                    })
+                    .collect::<Option<Vec<_>>>()?;
+                Area { frames, ..*area }
+            };
+            child.map_area(&area)?;
+            child.areas.push(area);
+        }
+        Some(())
+    }
+
+    // Drop every area, freeing the frames no other process shares
+    pub fn unmap_all(&mut self) {
+        for area in core::mem::take(&mut self.areas) {
+            self.root.unmap(area.start, area.end() - area.start);
+        }
+        self.asid.flush_all();
+    }
+
+    // Bytes backed by the frames of the areas
+    pub fn mapped_size(&self) -> u64 {
+        self.areas.iter().map(|area| area.end() - area.start).sum()
+    }
+
+    // Whether every page of len bytes at addr is user memory EL0 may read,
+    // and write as well if write is set
+    pub fn access_ok(&self, addr: u64, len: usize, write: bool) -> bool {
+        let end = match addr.checked_add(len as u64) {
+            Some(end) => end,
+            None => return false,
+        };
+        let mut page = addr & !(PAGE_SIZE - 1);
+        while page < end {
+            if user_ptr(self.root.phys(), page, write).is_none() {
+                return false;
+            }
+            page += PAGE_SIZE;
+        }
+        true
+    }
+
+    // Call f with the kernel view of each page sized piece of len bytes at
+    // addr and its offset in the range, once the whole range checked out.
+    // Consecutive user pages are not physically contiguous.
+    fn for_each_chunk(
+        &self,
+        addr: u64,
+        len: usize,
+        write: bool,
+        mut f: impl FnMut(*mut u8, usize, usize),
+    ) -> Option<()> {
+        if !self.access_ok(addr, len, write) {
+            return None;
+        }
+        let mut done = 0;
+        while done < len {
+            let cur = addr + done as u64;
+            let chunk = ((PAGE_SIZE - cur % PAGE_SIZE) as usize).min(len - done);
+            f(user_ptr(self.root.phys(), cur, write)?, done, chunk);
+            done += chunk;
+        }
+        Some(())
+    }
+
+    // None, with nothing copied, unless all of the destination is writable
+    // user memory
+    pub fn copy_to_user(&self, addr: u64, data: &[u8]) -> Option<()> {
+        self.for_each_chunk(addr, data.len(), true, |dst, offset, len| unsafe {
+            core::ptr::copy_nonoverlapping(data[offset..].as_ptr(), dst, len);
+        })
+    }
+
+    // None unless all of the source is readable user memory
+    pub fn copy_from_user(&self, addr: u64, buf: &mut [u8]) -> Option<()> {
+        self.for_each_chunk(addr, buf.len(), false, |src, offset, len| unsafe {
+            core::ptr::copy_nonoverlapping(src, buf[offset..].as_mut_ptr(), len);
+        })
+    }
+
+    // Copy a value to user memory, T must have no padding
+    pub fn put_user<T: Copy>(&self, addr: u64, value: &T) -> Option<()> {
+        let bytes =
+            unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+        self.copy_to_user(addr, bytes)
+    }
+
+    // Copy a value from user memory. Safety: whatever bytes the user left
+    // there must make a valid T.
+    pub unsafe fn get_user<T: Copy>(&self, addr: u64) -> Option<T> {
+        let mut value = MaybeUninit::<T>::zeroed();
+        let bytes = core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>());
+        self.copy_from_user(addr, bytes)?;
+        Some(value.assume_init())
     }
-}
 
-#[allow(dead_code)]
-unsafe fn dump(addr: *mut u64, level: u8) {
-    println!("  addr: 0x{:x}", addr as u64);
-    for i in 0..512 {
-        let p = addr.add(i);
-        if *p & 0b1 == 1 && level < 3 {
-            println!("    [{:03}] = 0x{:016x}", i, *p);
-        }
-    }
-    for i in 0..512 {
-        let p = addr.add(i);
-        if *p & 0b1 == 1 {
-            if *p & 0b11 == 0b11 {
-                if level < 3 {
-                    dump((*p & !0xfff) as *mut u64, level + 1);
-                } else {
-                    println!("    [{:03}] = 0x{:016x}", i, *p);
+    // The NUL terminated string at addr, without the NUL. None when it runs
+    // into memory EL0 may not read, or has no NUL in its first max bytes.
+    pub fn strncpy_from_user(&self, addr: u64, max: usize) -> Option<Vec<u8>> {
+        let mut ret = Vec::new();
+        while ret.len() < max {
+            let cur = addr + ret.len() as u64;
+            let src = user_ptr(self.root.phys(), cur, false)?;
+            let chunk = ((PAGE_SIZE - cur % PAGE_SIZE) as usize).min(max - ret.len());
+            let bytes = unsafe { core::slice::from_raw_parts(src, chunk) };
+            match bytes.iter().position(|&c| c == 0) {
+                Some(len) => {
+                    ret.extend_from_slice(&bytes[..len]);
+                    return Some(ret);
                 }
+                None => ret.extend_from_slice(bytes),
             }
         }
+        None
+    }
+
+    #[allow(dead_code)]
+    pub fn dump(&self) {
+        println!("VirtualMemory:");
+        self.root.dump();
     }
 }
 
-impl Clone for VirtualMemory {
-    fn clone(&self) -> Self {
-        VirtualMemory {
-            root: self.root.clone(),
-        }
+impl Drop for VirtualMemory {
+    // The tables are freed next, nothing may walk them anymore
+    fn drop(&mut self) {
+        self.asid.retire(self.root.phys());
     }
 }
@@ -0,0 +1,55 @@
+// Out of memory handling. User requests that would leave fewer than
+// RESERVE_FRAMES free fail with ENOMEM, keeping the rest for the kernel's
+// own allocations. Should free memory still drop below CRITICAL_FRAMES, the
+// user process with the largest RSS is killed on the way back to EL0.
+
+use crate::allocator::buddy::BUDDY_SYSTEM;
+use crate::allocator::config::FRAME_SIZE;
+use crate::scheduler::{self, INIT_PID};
+use crate::signal;
+use crate::thread::state::State;
+use stdio::error;
+
+const RESERVE_FRAMES: usize = 512;
+const CRITICAL_FRAMES: usize = 128;
+
+fn free_frames() -> usize {
+    unsafe { BUDDY_SYSTEM.free_frames() }
+}
+
+// Whether a user request for bytes more memory may go ahead
+pub fn can_allocate(bytes: u64) -> bool {
+    let frames = bytes.div_ceil(FRAME_SIZE as u64) as usize;
+    free_frames() >= frames + RESERVE_FRAMES
+}
+
+pub fn check() {
+    if free_frames() >= CRITICAL_FRAMES {
+        return;
+    }
+    let sched = scheduler::get();
+    let alive = || {
+        sched
+            .threads
+            .iter()
+            .flatten()
+            .filter(|thread| !matches!(thread.state, State::Zombie))
+    };
+    // A victim killed before is still on its way out
+    if alive().any(|thread| thread.signal.pending & (1 << signal::SIGKILL) != 0) {
+        return;
+    }
+    let victim = alive()
+        .filter(|thread| thread.id != INIT_PID)
+        .max_by_key(|thread| thread.memory_usage());
+    if let Some(victim) = victim {
+        error!(
+            "Out of memory: killing process {} ({}) with {} KiB RSS, {} KiB free",
+            victim.id,
+            victim.name,
+            victim.memory_usage() / 1024,
+            free_frames() * FRAME_SIZE / 1024
+        );
+        signal::send(victim.id, signal::SIGKILL);
+    }
+}
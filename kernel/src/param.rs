@@ -0,0 +1,141 @@
+// Kernel parameters parsed from the command line in the DTB /chosen/bootargs,
+// e.g. "init=/init loglevel=4 sched.slice_ms=31 buddy.verbose=1". A bare
+// name sets a boolean parameter.
+
+use crate::dtb;
+use alloc::format;
+use alloc::string::String;
+use stdio::log::Level;
+use stdio::{info, warn};
+
+pub struct Params {
+    // Program in the initramfs run as PID 1
+    pub init: String,
+    // Console log level, the built-in default is kept if None
+    pub loglevel: Option<Level>,
+    pub sched_slice_ms: u64,
+    pub buddy_verbose: bool,
+    // The command line as found in the DTB
+    pub cmdline: String,
+}
+
+impl Params {
+    fn new() -> Self {
+        Params {
+            init: String::from("init"),
+            loglevel: None,
+            sched_slice_ms: 31,
+            buddy_verbose: false,
+            cmdline: String::new(),
+        }
+    }
+}
+
+struct Param {
+    name: &'static str,
+    description: &'static str,
+    // Returns false if the value is invalid
+    set: fn(&mut Params, &str) -> bool,
+    show: fn(&Params) -> String,
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "1" | "y" | "yes" | "on" | "true" => Some(true),
+        "0" | "n" | "no" | "off" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+const REGISTRY: [Param; 4] = [
+    Param {
+        name: "init",
+        description: "program run as PID 1",
+        set: |params, value| {
+            // The initramfs has no leading slash in its file names
+            params.init = String::from(value.trim_start_matches('/'));
+            !params.init.is_empty()
+        },
+        show: |params| params.init.clone(),
+    },
+    Param {
+        name: "loglevel",
+        description: "console log level, 1 (error) to 5 (trace)",
+        set: |params, value| match Level::parse(value) {
+            Some(level) => {
+                params.loglevel = Some(level);
+                true
+            }
+            None => false,
+        },
+        show: |params| match params.loglevel {
+            Some(level) => format!("{}", level),
+            None => String::from("default"),
+        },
+    },
+    Param {
+        name: "sched.slice_ms",
+        description: "scheduler time slice in milliseconds",
+        set: |params, value| match value.parse::<u64>() {
+            Ok(ms) if ms > 0 => {
+                params.sched_slice_ms = ms;
+                true
+            }
+            _ => false,
+        },
+        show: |params| format!("{}", params.sched_slice_ms),
+    },
+    Param {
+        name: "buddy.verbose",
+        description: "trace every buddy allocator operation",
+        set: |params, value| match parse_bool(value) {
+            Some(verbose) => {
+                params.buddy_verbose = verbose;
+                true
+            }
+            None => false,
+        },
+        show: |params| format!("{}", params.buddy_verbose),
+    },
+];
+
+static mut PARAMS: Option<Params> = None;
+
+pub fn get() -> &'static Params {
+    unsafe { (*core::ptr::addr_of!(PARAMS)).as_ref().unwrap() }
+}
+
+fn parse(cmdline: &str) -> Params {
+    let mut params = Params::new();
+    params.cmdline = String::from(cmdline);
+    for arg in cmdline.split_whitespace() {
+        let (name, value) = arg.split_once('=').unwrap_or((arg, "1"));
+        match REGISTRY.iter().find(|param| param.name == name) {
+            Some(param) => {
+                if !(param.set)(&mut params, value) {
+                    warn!("Invalid value for {}: {}", name, value);
+                }
+            }
+            // Unknown parameters may be meant for init
+            None => info!("Ignoring unknown kernel parameter {}", arg),
+        }
+    }
+    params
+}
+
+pub fn init() {
+    let cmdline = dtb::get_bootargs().unwrap_or_default();
+    let params = parse(&cmdline);
+    unsafe {
+        PARAMS = Some(params);
+    }
+    info!("Kernel command line: {}", cmdline);
+}
+
+// Calls f with the name, current value and description of every parameter
+pub fn for_each(mut f: impl FnMut(&str, &str, &str)) {
+    let params = get();
+    for param in REGISTRY.iter() {
+        f(param.name, &(param.show)(params), param.description);
+    }
+}
@@ -1,14 +1,23 @@
 use crate::exception::trap_frame::TRAP_FRAME;
+use crate::file::FileTable;
+use crate::oom;
+use crate::param;
+use crate::signal;
+use crate::thread::state;
 use crate::thread::Thread;
-use alloc::alloc::alloc;
-use alloc::alloc::Layout;
+use crate::thread::MAX_STACK_SIZE;
+use crate::timer::{self, idle};
+use abi::types::{ProcInfo, COMM_LEN};
+use abi::types::{PROC_RUNNING, PROC_SLEEPING, PROC_STOPPED, PROC_ZOMBIE};
+use abi::Errno;
 use alloc::boxed::Box;
 use alloc::collections::VecDeque;
 use alloc::string::String;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::arch::asm;
 use core::time::Duration;
-use stdio::println;
+use stdio::{debug, trace};
 
 pub struct Scheduler {
     pub current: Option<usize>,
@@ -16,22 +25,39 @@ pub struct Scheduler {
     pub ready_queue: VecDeque<usize>,
     #[allow(dead_code)]
     pub wait_queue: VecDeque<usize>,
+    tick_enabled: bool,
+    // Counter value when the current thread was switched in
+    switched_at: u64,
 }
 
+// User stack size unless exec asks for another one, up to MAX_STACK_SIZE
 const STACK_SIZE: usize = 0x4000;
 
+// Orphaned threads are adopted by init, which is always the first user thread
+pub const INIT_PID: usize = 1;
+
+pub enum Wait {
+    Exited(usize, u64),
+    Running,
+    NoChild,
+}
+
 impl Scheduler {
     fn new() -> Self {
         Scheduler {
             current: None,
-            threads: Vec::new(),
+            // PID 0 is never handed out
+            threads: vec![None],
             ready_queue: VecDeque::new(),
             wait_queue: VecDeque::new(),
+            tick_enabled: false,
+            switched_at: 0,
         }
     }
 
     fn add_thread(&mut self, mut thread: Box<Thread>) -> usize {
-        let pos = self.threads.iter().position(|t| t.is_none());
+        let pos = self.threads.iter().skip(1).position(|t| t.is_none());
+        let pos = pos.map(|index| index + 1);
         match pos {
             Some(index) => {
                 thread.id = index;
@@ -46,6 +72,10 @@ impl Scheduler {
         }
     }
 
+    pub fn current_thread(&mut self) -> &mut Thread {
+        self.threads[self.current.unwrap()].as_mut().unwrap()
+    }
+
     pub fn save_current(&mut self) -> usize {
         let current = self.current.unwrap();
         unsafe {
@@ -54,11 +84,30 @@ impl Scheduler {
         current
     }
 
+    // Charge the time since the last switch to the running thread
+    fn charge_current(&mut self) {
+        let now = timer::manager::get().get_current();
+        if let Some(current) = self.current {
+            if let Some(thread) = self.threads[current].as_mut() {
+                thread.stats.cpu_ticks += now - self.switched_at;
+            }
+        }
+        self.switched_at = now;
+    }
+
+    fn switch_in(&mut self, tid: usize) {
+        self.threads[tid].as_mut().unwrap().stats.switches += 1;
+        self.switched_at = timer::manager::get().get_current();
+    }
+
     fn restore_next(&mut self) -> usize {
         if let Some(next) = self.ready_queue.pop_front() {
+            let thread = self.threads[next].as_mut().unwrap();
+            thread.cpu_state.l0 = thread.vm.ttbr0();
             unsafe {
-                TRAP_FRAME.as_mut().unwrap().state = self.threads[next].as_ref().unwrap().cpu_state;
+                TRAP_FRAME.as_mut().unwrap().state = thread.cpu_state;
             }
+            self.switch_in(next);
             next
         } else {
             panic!("No thread to restore");
@@ -72,6 +121,7 @@ impl Scheduler {
             // println!("No thread to schedule");
             return;
         }
+        self.charge_current();
         let current = self.save_current();
         let next = self.restore_next();
         self.current = Some(next);
@@ -79,39 +129,70 @@ impl Scheduler {
         // println!("Switching from {} to {}", current, next);
     }
 
-    pub fn create_thread(&mut self, entry: *mut u8, len: usize) {
-        let thread = Box::new(Thread::new(STACK_SIZE, entry, len));
-        println!("Creating thread");
+    // ENOMEM if there is no memory to load the program and E2BIG if the
+    // arguments do not fit on the stack
+    pub fn create_thread(&mut self, image: &[u8], args: &[String]) -> Result<usize, Errno> {
+        if !oom::can_allocate(image.len() as u64 + STACK_SIZE as u64) {
+            return Err(Errno::ENOMEM);
+        }
+        let mut thread = Box::new(Thread::new(STACK_SIZE, image).ok_or(Errno::ENOMEM)?);
+        thread.set_args(args).ok_or(Errno::E2BIG)?;
         let tid = self.add_thread(thread);
-        println!("Created thread {}", tid);
+        debug!("Created thread {}", tid);
         self.ready_queue.push_back(tid);
-        println!("Number of threads: {}", self.threads.len());
+        Ok(tid)
     }
 
     pub fn sched_timer(&mut self) {
         let tm = crate::timer::manager::get();
         tm.add_timer(
-            Duration::from_secs_f64(1 as f64 / (1 << 5) as f64),
+            Duration::from_millis(param::get().sched_slice_ms),
             Box::new(|| {
-                get().schedule();
-                get().sched_timer();
+                get().tick();
             }),
         );
     }
 
-    pub fn run_threads(&mut self) -> ! {
+    // Restart the periodic tick once there is more than one runnable thread
+    fn start_tick(&mut self) {
+        if self.tick_enabled || self.current.is_none() || self.ready_queue.is_empty() {
+            return;
+        }
+        self.tick_enabled = true;
         self.sched_timer();
+    }
+
+    fn tick(&mut self) {
+        idle::get().ticks_fired += 1;
+        if self.current.is_none() {
+            self.tick_enabled = false;
+            return;
+        }
+        self.schedule();
+        if self.ready_queue.is_empty() {
+            // Nothing to switch to, stay tickless until another thread is ready
+            self.tick_enabled = false;
+            idle::get().ticks_stopped += 1;
+        } else {
+            self.sched_timer();
+        }
+    }
+
+    pub fn run_threads(&mut self) -> ! {
         assert!(self.current.is_none());
         assert!(!self.ready_queue.is_empty());
-        println!("Ready queue: {:?}", self.ready_queue);
+        trace!("Ready queue: {:?}", self.ready_queue);
         let next = self.ready_queue.pop_front().unwrap();
         self.current = Some(next);
-        println!("Switching to {}", next);
-        let thread = self.threads[next].as_ref().unwrap();
+        self.switch_in(next);
+        self.start_tick();
+        debug!("Switching to {}", next);
+        let thread = self.threads[next].as_mut().unwrap();
         assert!(thread.id == next);
+        thread.cpu_state.l0 = thread.vm.ttbr0();
         let pc = thread.cpu_state.pc;
-        let sp = thread.stack as usize + thread.stack_size;
-        println!("{:?}", thread.cpu_state);
+        let sp = thread.cpu_state.sp;
+        trace!("{:?}", thread.cpu_state);
         unsafe {
             asm!(
                 "mrs {0}, cntkctl_el1",
@@ -125,70 +206,234 @@ impl Scheduler {
                 "msr sp_el0, {1}",
                 "dsb ish",
                 "msr ttbr0_el1, {2}",
-                "tlbi vmalle1is",
-                "dsb ish",
                 "isb",
                 "eret",
                 in(reg) pc,
                 in(reg) sp,
-                in(reg) thread.vm.get_l0_addr(),
+                in(reg) thread.cpu_state.l0,
+                in("x0") thread.cpu_state.x[0],
+                in("x1") thread.cpu_state.x[1],
                 options(noreturn),
             );
         }
     }
 
-    pub fn exec(&mut self, name: String) {
+    // The current program is left untouched on failure, with ENOENT if name
+    // is not found, ENOMEM if there is no memory to load it and EINVAL if the
+    // stack size is too large. A stack size of 0 picks the default.
+    pub fn exec(
+        &mut self,
+        name: String,
+        args: Vec<String>,
+        stack_size: usize,
+    ) -> Result<(), Errno> {
         let current = self.current.unwrap();
+        let stack_size = match stack_size {
+            0 => STACK_SIZE,
+            size if size > MAX_STACK_SIZE => return Err(Errno::EINVAL),
+            size => (size + 0xfff) & !0xfff,
+        };
         let program =
             filesystem::cpio::CpioArchive::load(unsafe { crate::INITRAMFS_ADDR } as *const u8);
         if let Some(data) = program.get_file(name.as_str()) {
-            let program = alloc_prog(data);
-            let new_thread = Box::new(Thread::new(STACK_SIZE, program.0, program.1));
+            if !oom::can_allocate(data.len() as u64 + stack_size as u64) {
+                return Err(Errno::ENOMEM);
+            }
+            let mut new_thread = Box::new(Thread::new(stack_size, data).ok_or(Errno::ENOMEM)?);
+            self.charge_current();
+            new_thread.set_args(&args).ok_or(Errno::E2BIG)?;
+            let old_thread = self.threads[current].as_mut().unwrap();
+            new_thread.id = current;
+            new_thread.name = name;
+            new_thread.stats = old_thread.stats;
+            new_thread.parent = old_thread.parent;
+            new_thread.signal.blocked = old_thread.signal.blocked;
+            // Open files survive exec
+            new_thread.files = core::mem::replace(&mut old_thread.files, FileTable::new());
             self.threads[current] = Some(new_thread);
             self.ready_queue.push_back(current);
             let next = self.restore_next();
             self.current = Some(next);
+            Ok(())
         } else {
-            println!("File not found: {}", name);
+            debug!("exec: file not found: {}", name);
+            Err(Errno::ENOENT)
         }
     }
 
-    pub fn fork(&mut self) -> u64 {
+    // None when there is no memory for the child
+    pub fn fork(&mut self) -> Option<u64> {
         let current = self.save_current();
-        let mut new_thread = self.threads[current].as_ref().unwrap().clone();
+        let parent = self.threads[current].as_ref().unwrap();
+        if !oom::can_allocate(parent.memory_usage()) {
+            return None;
+        }
+        let mut new_thread = Box::new(parent.try_clone()?);
         new_thread.cpu_state.x[0] = 0;
-        println!("New thread cpu_state {:?}", new_thread.cpu_state);
+        new_thread.parent = Some(current);
+        trace!("New thread cpu_state {:?}", new_thread.cpu_state);
         let tid = self.add_thread(new_thread) as u64;
-        println!("Forked thread 0x{:x}", tid);
+        debug!("Forked thread {} from {}", tid, current);
         self.ready_queue.push_back(tid as usize);
-        tid
+        self.start_tick();
+        Some(tid)
     }
 
     pub fn exit(&mut self, status: u64) {
         let current = self.current.unwrap();
-        // self.threads[current].as_mut().unwrap().state = state::State::Zombie;
-        // self.zombie_queue.push_back(current);
-        println!("Thread {} exited with status {}", current, status);
+        debug!("Thread {} exited with status {}", current, status);
+        self.charge_current();
+        self.reparent_children(current);
+        let parent = self.threads[current].as_ref().unwrap().parent;
+        let parent = parent.filter(|&parent| self.threads[parent].is_some());
         self.current = None;
-        self.sched_timer();
-        if self.ready_queue.is_empty() {
-            panic!("All threads exited");
+        match parent {
+            Some(parent) => {
+                let thread = self.threads[current].as_mut().unwrap();
+                // Close the files right away so that pipe peers see EOF, and
+                // release the memory rather than wait for the parent
+                thread.files = FileTable::new();
+                thread.vm.unmap_all();
+                thread.state = state::State::Zombie;
+                thread.exit_status = status;
+                signal::send(parent, signal::SIGCHLD);
+            }
+            None => self.threads[current] = None,
         }
-        let next = self.restore_next();
-        self.current = Some(next);
+        self.switch_to_next();
     }
 
-    pub fn kill(&mut self, tid: usize) {
-        let current = self.save_current();
-        if tid == current {
-            println!("Killing current thread");
-            self.exit(0);
+    // Hand the children of an exiting thread over to init, or reap the
+    // exited ones right away when there is no init to collect them
+    fn reparent_children(&mut self, tid: usize) {
+        let adopter = match self.threads.get(INIT_PID) {
+            Some(Some(_)) if tid != INIT_PID => Some(INIT_PID),
+            _ => None,
+        };
+        let mut zombie_adopted = false;
+        for slot in self.threads.iter_mut() {
+            let child = match slot {
+                Some(child) if child.parent == Some(tid) => child,
+                _ => continue,
+            };
+            child.parent = adopter;
+            if let state::State::Zombie = child.state {
+                if adopter.is_some() {
+                    zombie_adopted = true;
+                } else {
+                    *slot = None;
+                }
+            }
+        }
+        if zombie_adopted {
+            self.wake(INIT_PID);
+        }
+    }
+
+    // Collect an exited child of the current thread, any child if pid is None
+    pub fn wait(&mut self, pid: Option<usize>) -> Wait {
+        let current = self.current.unwrap();
+        let mut running = false;
+        for tid in 0..self.threads.len() {
+            let child = match &self.threads[tid] {
+                Some(child) if child.parent == Some(current) => child,
+                _ => continue,
+            };
+            if pid.is_some_and(|pid| pid != tid) {
+                continue;
+            }
+            if let state::State::Zombie = child.state {
+                let status = child.exit_status;
+                self.threads[tid] = None;
+                return Wait::Exited(tid, status);
+            }
+            running = true;
+        }
+        if running {
+            Wait::Running
         } else {
-            println!("Killing thread {}", tid);
-            self.threads[tid] = None;
-            self.ready_queue.retain(|&t| t != tid);
+            Wait::NoChild
         }
     }
+
+    pub fn stop_current(&mut self) {
+        self.charge_current();
+        let current = self.save_current();
+        self.threads[current].as_mut().unwrap().state = state::State::Stopped;
+        self.current = None;
+        self.switch_to_next();
+    }
+
+    pub fn resume(&mut self, tid: usize) {
+        let thread = self.threads[tid].as_mut().unwrap();
+        if let state::State::Stopped = thread.state {
+            thread.state = state::State::Ready;
+            self.ready_queue.push_back(tid);
+            self.start_tick();
+        }
+    }
+
+    pub fn block_current(&mut self) {
+        self.charge_current();
+        let current = self.save_current();
+        self.threads[current].as_mut().unwrap().state = state::State::Blocked;
+        self.current = None;
+        self.switch_to_next();
+    }
+
+    pub fn wake(&mut self, tid: usize) {
+        if let Some(Some(thread)) = self.threads.get_mut(tid) {
+            if let state::State::Blocked = thread.state {
+                thread.state = state::State::Ready;
+                self.ready_queue.push_back(tid);
+                self.start_tick();
+            }
+        }
+    }
+
+    // Snapshot of every thread for ps and top
+    pub fn procinfo(&mut self) -> Vec<ProcInfo> {
+        self.charge_current();
+        let freq = timer::manager::get().get_frequency() as u128;
+        let mut ret = Vec::new();
+        for thread in self.threads.iter().flatten() {
+            let state = match thread.state {
+                state::State::Ready => PROC_RUNNING,
+                state::State::Blocked => PROC_SLEEPING,
+                state::State::Stopped => PROC_STOPPED,
+                state::State::Zombie => PROC_ZOMBIE,
+            };
+            let mut info = ProcInfo {
+                pid: thread.id as u64,
+                ppid: thread.parent.unwrap_or(0) as u64,
+                cpu_ns: (thread.stats.cpu_ticks as u128 * 1_000_000_000 / freq) as u64,
+                switches: thread.stats.switches,
+                mem: thread.memory_usage(),
+                state,
+                ..Default::default()
+            };
+            let len = thread.name.len().min(COMM_LEN - 1);
+            info.name[..len].copy_from_slice(&thread.name.as_bytes()[..len]);
+            ret.push(info);
+        }
+        ret
+    }
+
+    // Switch to the next ready thread, idling until one becomes ready
+    fn switch_to_next(&mut self) {
+        while self.ready_queue.is_empty() {
+            if self.threads.iter().all(|t| t.is_none()) {
+                panic!("All threads exited");
+            }
+            idle::wait_for_interrupt();
+            unsafe {
+                crate::exception::irq_dispatch();
+            }
+        }
+        let next = self.restore_next();
+        self.current = Some(next);
+        self.start_tick();
+    }
 }
 
 static mut SCHEDULER: Option<Scheduler> = None;
@@ -202,22 +447,3 @@ pub fn init() {
         SCHEDULER = Some(Scheduler::new());
     }
 }
-
-pub fn alloc_prog(data: &[u8]) -> (*mut u8, usize) {
-    let program_entry = unsafe { alloc(Layout::from_size_align(data.len(), 0x1000).unwrap()) };
-    unsafe {
-        core::ptr::write_bytes(program_entry, 0, data.len());
-    }
-    println!(
-        "Executing program at 0x{:x}-0x{:x}",
-        program_entry as usize,
-        program_entry as usize + data.len()
-    );
-    println!("Program size: 0x{:x} bytes", data.len());
-    println!("Program entry: {:p}", program_entry);
-    // let program_entry: extern "C" fn() = unsafe { core::mem::transmute(program_entry) };
-    unsafe {
-        core::ptr::copy(data.as_ptr(), program_entry as *mut u8, data.len());
-    }
-    (program_entry, data.len())
-}
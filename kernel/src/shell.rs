@@ -0,0 +1,14 @@
+mod complete;
+mod editor;
+mod history;
+
+use crate::commands;
+use editor::LineEditor;
+
+// The kernel debug console, used when there is no init in the initramfs
+pub fn run() -> ! {
+    loop {
+        let line = LineEditor::new("> ").read_line();
+        commands::execute(line.as_bytes());
+    }
+}
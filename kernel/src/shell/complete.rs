@@ -0,0 +1,42 @@
+use crate::commands;
+use crate::INITRAMFS_ADDR;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use filesystem::cpio::CpioArchive;
+
+// Command names complete the first word, initramfs files the others
+pub fn candidates(prefix: &str, first_word: bool) -> Vec<String> {
+    let mut ret: Vec<String> = if first_word {
+        commands::commands()
+            .iter()
+            .map(|command| command.name().to_string())
+            .collect()
+    } else {
+        let rootfs = CpioArchive::load(unsafe { INITRAMFS_ADDR } as *const u8);
+        rootfs
+            .files()
+            .map(|(name, _)| name.to_string())
+            .filter(|name| name != ".")
+            .collect()
+    };
+    ret.retain(|name| name.starts_with(prefix));
+    ret.sort();
+    ret
+}
+
+pub fn common_prefix(words: &[String]) -> &str {
+    let first = match words.first() {
+        Some(first) => first.as_str(),
+        None => return "",
+    };
+    let mut len = first.len();
+    for word in words.iter().skip(1) {
+        len = first
+            .bytes()
+            .zip(word.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count();
+    }
+    &first[..len]
+}
@@ -0,0 +1,313 @@
+use super::{complete, history};
+use crate::timer;
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+use driver::uart;
+use stdio::{print, println};
+
+const MAX_LINE_LEN: usize = 0x100;
+// A terminal sends an escape sequence at once, a lone ESC is followed by
+// nothing for longer than this
+const ESC_TIMEOUT: Duration = Duration::from_millis(50);
+
+#[derive(Clone, Copy, PartialEq)]
+enum Key {
+    Char(u8),
+    Ctrl(u8),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Unknown,
+}
+
+fn read_key() -> Key {
+    match uart::recv() {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        b'\x7f' | b'\x08' => Key::Backspace,
+        b'\x1b' => read_escape(),
+        c @ 0x01..=0x1a => Key::Ctrl(c - 1 + b'a'),
+        c @ 0x20..=0x7e => Key::Char(c),
+        _ => Key::Unknown,
+    }
+}
+
+// The next byte of an escape sequence, None when it does not come in time
+fn recv_escape() -> Option<u8> {
+    let tm = timer::manager::get();
+    let deadline = tm.get_current() + tm.to_ticks(ESC_TIMEOUT);
+    loop {
+        if let Some(c) = uart::recv_nb() {
+            return Some(c);
+        }
+        if tm.get_current() >= deadline {
+            return None;
+        }
+    }
+}
+
+// VT100 sequences: ESC [ A-D for the arrows, ESC [ H/F or ESC O H/F and
+// ESC [ n ~ for Home, End and Delete
+fn read_escape() -> Key {
+    match recv_escape() {
+        Some(b'[') => {}
+        Some(b'O') => {
+            return match recv_escape() {
+                Some(b'H') => Key::Home,
+                Some(b'F') => Key::End,
+                _ => Key::Unknown,
+            }
+        }
+        _ => return Key::Unknown,
+    }
+    match recv_escape() {
+        Some(b'A') => Key::Up,
+        Some(b'B') => Key::Down,
+        Some(b'C') => Key::Right,
+        Some(b'D') => Key::Left,
+        Some(b'H') => Key::Home,
+        Some(b'F') => Key::End,
+        Some(c @ b'0'..=b'9') => {
+            let mut n = (c - b'0') as u32;
+            loop {
+                match recv_escape() {
+                    Some(d @ b'0'..=b'9') => n = n * 10 + (d - b'0') as u32,
+                    Some(b'~') => break,
+                    _ => return Key::Unknown,
+                }
+            }
+            match n {
+                1 | 7 => Key::Home,
+                4 | 8 => Key::End,
+                3 => Key::Delete,
+                _ => Key::Unknown,
+            }
+        }
+        _ => Key::Unknown,
+    }
+}
+
+pub struct LineEditor<'a> {
+    prompt: &'a str,
+    buf: Vec<u8>,
+    cursor: usize,
+    // Entry shown while browsing the history, and the line typed before
+    hist_pos: Option<usize>,
+    draft: Vec<u8>,
+}
+
+impl<'a> LineEditor<'a> {
+    pub fn new(prompt: &'a str) -> Self {
+        LineEditor {
+            prompt,
+            buf: Vec::new(),
+            cursor: 0,
+            hist_pos: None,
+            draft: Vec::new(),
+        }
+    }
+
+    // Editing works on bytes, a character cut in half shows as U+FFFD
+    fn line(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.buf)
+    }
+
+    // Redraw the whole line and put the cursor back in place
+    fn refresh(&self) {
+        print!("\r{}{}\x1b[K", self.prompt, self.line());
+        let back = self.buf.len() - self.cursor;
+        if back > 0 {
+            print!("\x1b[{}D", back);
+        }
+    }
+
+    fn set_line(&mut self, line: &[u8]) {
+        self.buf = line.to_vec();
+        self.cursor = self.buf.len();
+    }
+
+    // What does not fit is dropped, at a character boundary
+    fn insert(&mut self, s: &[u8]) {
+        let mut len = s.len().min(MAX_LINE_LEN.saturating_sub(self.buf.len()));
+        while len < s.len() && s[len] & 0xc0 == 0x80 {
+            len -= 1;
+        }
+        self.buf
+            .splice(self.cursor..self.cursor, s[..len].iter().copied());
+        self.cursor += len;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.buf.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.buf.len() {
+            self.buf.remove(self.cursor);
+        }
+    }
+
+    // Ctrl-W removes the word before the cursor along with the spaces after it
+    fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.buf[start - 1] == b' ' {
+            start -= 1;
+        }
+        while start > 0 && self.buf[start - 1] != b' ' {
+            start -= 1;
+        }
+        self.buf.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    fn history_prev(&mut self) {
+        let history = history::get();
+        let next = self.hist_pos.map_or(0, |pos| pos + 1);
+        if let Some(line) = history.get(next) {
+            if self.hist_pos.is_none() {
+                self.draft = self.buf.clone();
+            }
+            self.hist_pos = Some(next);
+            self.set_line(line.as_bytes());
+        }
+    }
+
+    fn history_next(&mut self) {
+        match self.hist_pos {
+            None => {}
+            Some(0) => {
+                self.hist_pos = None;
+                let draft = core::mem::take(&mut self.draft);
+                self.set_line(&draft);
+            }
+            Some(pos) => {
+                self.hist_pos = Some(pos - 1);
+                let line = history::get().get(pos - 1).unwrap();
+                self.set_line(line.as_bytes());
+            }
+        }
+    }
+
+    fn complete(&mut self) {
+        let before = &self.buf[..self.cursor];
+        let start = before
+            .iter()
+            .rposition(|&c| c == b' ' || c == b'|')
+            .map_or(0, |i| i + 1);
+        let first_word = before[..start].iter().all(|&c| c == b' ');
+        let prefix = String::from_utf8_lossy(&before[start..]).into_owned();
+        let candidates = complete::candidates(&prefix, first_word);
+        match candidates.len() {
+            0 => {}
+            1 => {
+                self.insert(&candidates[0].as_bytes()[prefix.len()..]);
+                self.insert(b" ");
+            }
+            _ => {
+                let common = complete::common_prefix(&candidates);
+                if common.len() > prefix.len() {
+                    self.insert(&common.as_bytes()[prefix.len()..]);
+                } else {
+                    println!();
+                    for candidate in candidates.iter() {
+                        print!("{}  ", candidate);
+                    }
+                    println!();
+                }
+            }
+        }
+    }
+
+    // Ctrl-R incremental search through the history. Returns the key that
+    // ended the search to be handled on the matched line, None if cancelled.
+    fn search(&mut self) -> Option<Key> {
+        let history = history::get();
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        loop {
+            let matched = found.and_then(|idx| history.get(idx)).unwrap_or("");
+            print!("\r(reverse-i-search)`{}': {}\x1b[K", query, matched);
+            match read_key() {
+                Key::Char(c) => {
+                    query.push(c as char);
+                    found = history.search(&query, found.unwrap_or(0));
+                }
+                Key::Backspace => {
+                    query.pop();
+                    found = history.search(&query, 0);
+                }
+                Key::Ctrl(b'r') => {
+                    if let Some(idx) = found {
+                        found = history.search(&query, idx + 1).or(found);
+                    }
+                }
+                Key::Ctrl(b'g') | Key::Ctrl(b'c') => return None,
+                key => {
+                    if let Some(idx) = found {
+                        self.hist_pos = None;
+                        self.set_line(history.get(idx).unwrap().as_bytes());
+                    }
+                    return Some(key);
+                }
+            }
+        }
+    }
+
+    pub fn read_line(mut self) -> String {
+        self.refresh();
+        loop {
+            let mut key = read_key();
+            if key == Key::Ctrl(b'r') {
+                key = match self.search() {
+                    Some(key) => key,
+                    None => Key::Unknown,
+                };
+            }
+            match key {
+                Key::Enter => {
+                    self.refresh();
+                    println!();
+                    let line = self.line().into_owned();
+                    history::get().push(&line);
+                    return line;
+                }
+                Key::Char(c) => self.insert(&[c]),
+                Key::Tab => self.complete(),
+                Key::Backspace => self.backspace(),
+                Key::Delete | Key::Ctrl(b'd') => self.delete(),
+                Key::Left | Key::Ctrl(b'b') => self.cursor = self.cursor.saturating_sub(1),
+                Key::Right | Key::Ctrl(b'f') => self.cursor = (self.cursor + 1).min(self.buf.len()),
+                Key::Home | Key::Ctrl(b'a') => self.cursor = 0,
+                Key::End | Key::Ctrl(b'e') => self.cursor = self.buf.len(),
+                Key::Up | Key::Ctrl(b'p') => self.history_prev(),
+                Key::Down | Key::Ctrl(b'n') => self.history_next(),
+                Key::Ctrl(b'k') => self.buf.truncate(self.cursor),
+                Key::Ctrl(b'u') => {
+                    self.buf.drain(..self.cursor);
+                    self.cursor = 0;
+                }
+                Key::Ctrl(b'w') => self.delete_word(),
+                Key::Ctrl(b'c') => {
+                    println!("^C");
+                    self.hist_pos = None;
+                    self.set_line(b"");
+                }
+                Key::Ctrl(b'l') => print!("\x1b[2J\x1b[H"),
+                _ => {}
+            }
+            self.refresh();
+        }
+    }
+}
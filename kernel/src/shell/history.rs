@@ -0,0 +1,47 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+
+const HISTORY_SIZE: usize = 64;
+
+// Ring of the last commands, kept across shell sessions
+pub struct History {
+    entries: VecDeque<String>,
+}
+
+impl History {
+    const fn new() -> Self {
+        History {
+            entries: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: &str) {
+        if line.trim().is_empty() || self.entries.back().is_some_and(|last| last == line) {
+            return;
+        }
+        if self.entries.len() == HISTORY_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(String::from(line));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    // Entry 0 is the most recent one
+    pub fn get(&self, idx: usize) -> Option<&str> {
+        self.entries.iter().rev().nth(idx).map(|line| line.as_str())
+    }
+
+    // Index of the most recent entry from idx on that contains query
+    pub fn search(&self, query: &str, idx: usize) -> Option<usize> {
+        (idx..self.len()).find(|&i| self.get(i).unwrap().contains(query))
+    }
+}
+
+static mut HISTORY: History = History::new();
+
+pub fn get() -> &'static mut History {
+    unsafe { &mut *core::ptr::addr_of_mut!(HISTORY) }
+}
@@ -0,0 +1,357 @@
+use crate::exception::trap_frame::TRAP_FRAME;
+use crate::scheduler;
+use crate::thread::cpu;
+use crate::thread::Thread;
+use abi::Errno;
+use alloc::boxed::Box;
+use core::arch::global_asm;
+use core::time::Duration;
+use stdio::info;
+
+pub use abi::signal::*;
+
+// SIGKILL and SIGSTOP can neither be caught, ignored nor blocked
+const UNBLOCKABLE: u64 = 1 << SIGKILL | 1 << SIGSTOP;
+
+// User address of the page holding the sigreturn trampoline, between the
+// mmap range and the lowest stack guard page
+pub const SIGRETURN_ADDR: u64 = 0xffff_0000_0000;
+
+global_asm!(
+    ".global sigreturn_trampoline",
+    ".global sigreturn_trampoline_end",
+    "sigreturn_trampoline:",
+    "mov x8, {sigreturn}",
+    "svc 0",
+    "sigreturn_trampoline_end:",
+    sigreturn = const abi::Sysno::Sigreturn as u64,
+);
+
+extern "C" {
+    static sigreturn_trampoline: u8;
+    static sigreturn_trampoline_end: u8;
+}
+
+pub fn trampoline() -> &'static [u8] {
+    unsafe {
+        let start = &sigreturn_trampoline as *const u8;
+        let end = &sigreturn_trampoline_end as *const u8;
+        core::slice::from_raw_parts(start, end as usize - start as usize)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+fn default_action(sig: usize) -> DefaultAction {
+    match sig {
+        SIGCHLD => DefaultAction::Ignore,
+        SIGCONT => DefaultAction::Continue,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ITimer {
+    id: u64,
+    expiry: u64,
+    interval: Duration,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SignalState {
+    pub pending: u64,
+    pub blocked: u64,
+    handlers: [u64; NSIG],
+    itimer: Option<ITimer>,
+}
+
+impl SignalState {
+    pub fn new() -> Self {
+        SignalState {
+            pending: 0,
+            blocked: 0,
+            handlers: [SIG_DFL; NSIG],
+            itimer: None,
+        }
+    }
+
+    // Handlers and the blocked mask are inherited by a forked child,
+    // pending signals and timers are not.
+    pub fn fork(&self) -> Self {
+        SignalState {
+            pending: 0,
+            itimer: None,
+            ..*self
+        }
+    }
+
+    fn next_pending(&self) -> Option<usize> {
+        let deliverable = self.pending & !self.blocked;
+        if deliverable == 0 {
+            None
+        } else {
+            Some(deliverable.trailing_zeros() as usize)
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    state: cpu::State,
+    blocked: u64,
+}
+
+fn valid(sig: usize) -> bool {
+    sig > 0 && sig < NSIG
+}
+
+pub fn sigaction(sig: usize, handler: u64) -> Option<u64> {
+    if !valid(sig) || UNBLOCKABLE & (1 << sig) != 0 {
+        return None;
+    }
+    let thread = scheduler::get().current_thread();
+    let old = thread.signal.handlers[sig];
+    thread.signal.handlers[sig] = handler;
+    Some(old)
+}
+
+pub fn sigprocmask(how: u64, set: u64) -> Option<u64> {
+    let thread = scheduler::get().current_thread();
+    let old = thread.signal.blocked;
+    let blocked = match how {
+        SIG_BLOCK => old | set,
+        SIG_UNBLOCK => old & !set,
+        SIG_SETMASK => set,
+        _ => return None,
+    };
+    thread.signal.blocked = blocked & !UNBLOCKABLE;
+    Some(old)
+}
+
+pub fn send(tid: usize, sig: usize) -> bool {
+    if !valid(sig) {
+        return false;
+    }
+    // Like Linux, init only gets the signals it handles, the system cannot
+    // go on without it
+    if tid == scheduler::INIT_PID {
+        match scheduler::get().threads.get(tid) {
+            Some(Some(thread)) if !init_accepts(thread, sig) => return true,
+            _ => {}
+        }
+    }
+    deliver(tid, sig)
+}
+
+fn init_accepts(thread: &Thread, sig: usize) -> bool {
+    if UNBLOCKABLE & 1 << sig != 0 {
+        return false;
+    }
+    thread.signal.handlers[sig] != SIG_DFL
+        || matches!(
+            default_action(sig),
+            DefaultAction::Ignore | DefaultAction::Continue
+        )
+}
+
+fn deliver(tid: usize, sig: usize) -> bool {
+    let sched = scheduler::get();
+    let thread = match sched.threads.get_mut(tid) {
+        Some(Some(thread)) => thread,
+        _ => return false,
+    };
+    if sig == SIGCONT {
+        // SIGCONT always resumes the thread, even if blocked or handled
+        thread.signal.pending &= !(1 << SIGSTOP | 1 << SIGTSTP | 1 << SIGTTIN | 1 << SIGTTOU);
+    } else if default_action(sig) == DefaultAction::Stop {
+        thread.signal.pending &= !(1 << SIGCONT);
+    }
+    thread.signal.pending |= 1 << sig;
+    if sig == SIGCONT || sig == SIGKILL {
+        sched.resume(tid);
+    }
+    // Interrupt a blocking syscall. It is restarted and checks again what it
+    // waits for, so a signal that is blocked or ignored only costs a retry,
+    // and wait relies on SIGCHLD for this even when it is ignored.
+    sched.wake(tid);
+    true
+}
+
+// Send a signal for a fault the thread cannot get past. Blocking or ignoring
+// it would only fault again on return, so it takes the default action then,
+// even in init.
+pub fn force(tid: usize, sig: usize) {
+    if let Some(Some(thread)) = scheduler::get().threads.get_mut(tid) {
+        let state = &mut thread.signal;
+        if state.blocked & 1 << sig != 0 || state.handlers[sig] == SIG_IGN {
+            state.blocked &= !(1 << sig);
+            state.handlers[sig] = SIG_DFL;
+        }
+    }
+    deliver(tid, sig);
+}
+
+// Deliver the pending signals of the current thread right before returning to EL0
+pub fn do_signal() {
+    let sched = scheduler::get();
+    while sched.current.is_some() {
+        let thread = sched.current_thread();
+        let sig = match thread.signal.next_pending() {
+            Some(sig) => sig,
+            None => return,
+        };
+        thread.signal.pending &= !(1 << sig);
+        match thread.signal.handlers[sig] {
+            SIG_IGN => continue,
+            SIG_DFL => match default_action(sig) {
+                DefaultAction::Terminate => {
+                    info!("Thread {} terminated by signal {}", thread.id, sig);
+                    sched.exit(128 + sig as u64);
+                }
+                DefaultAction::Stop => {
+                    info!("Thread {} stopped by signal {}", thread.id, sig);
+                    sched.stop_current();
+                }
+                DefaultAction::Ignore | DefaultAction::Continue => continue,
+            },
+            handler => {
+                // A nanosleep is not restarted after the handler, it fails
+                if thread.sleep_until.take().is_some() {
+                    let tf = unsafe { TRAP_FRAME.as_mut().unwrap() };
+                    tf.state.pc += 4;
+                    tf.state.x[0] = Errno::EINTR.to_ret();
+                }
+                if setup_frame(thread, sig, handler).is_none() {
+                    bad_frame(thread.id);
+                }
+                return;
+            }
+        }
+    }
+}
+
+// None when the frame does not fit on the user stack
+fn setup_frame(thread: &mut Thread, sig: usize, handler: u64) -> Option<()> {
+    let tf = unsafe { TRAP_FRAME.as_mut().unwrap() };
+    let frame = SignalFrame {
+        state: tf.state,
+        blocked: thread.signal.blocked,
+    };
+    let sp = tf
+        .state
+        .sp
+        .checked_sub(core::mem::size_of::<SignalFrame>() as u64)?
+        & !0xf;
+    thread.vm.put_user(sp, &frame)?;
+    thread.signal.blocked |= 1 << sig;
+    thread.signal.blocked &= !UNBLOCKABLE;
+    tf.state.x[0] = sig as u64;
+    tf.state.x[30] = SIGRETURN_ADDR;
+    tf.state.pc = handler;
+    tf.state.sp = sp;
+    Some(())
+}
+
+// The signal frame could not be written or read back, the thread cannot go on
+fn bad_frame(tid: usize) {
+    info!("Thread {} killed, bad signal frame", tid);
+    scheduler::get().exit(128 + SIGSEGV as u64);
+}
+
+pub fn sigreturn() {
+    let thread = scheduler::get().current_thread();
+    let tf = unsafe { TRAP_FRAME.as_mut().unwrap() };
+    // The frame is all integers, anything the handler left there is valid
+    let Some(frame) = (unsafe { thread.vm.get_user::<SignalFrame>(tf.state.sp) }) else {
+        bad_frame(thread.id);
+        return;
+    };
+    // Only the user visible registers are restored from the user stack
+    tf.state.x = frame.state.x;
+    tf.state.pc = frame.state.pc;
+    tf.state.sp = frame.state.sp;
+    thread.signal.blocked = frame.blocked & !UNBLOCKABLE;
+}
+
+static mut NEXT_ITIMER_ID: u64 = 0;
+
+fn ticks_to_duration(ticks: u64) -> Duration {
+    let freq = crate::timer::manager::get().get_frequency() as u128;
+    let micros = ticks as u128 * 1_000_000 / freq;
+    Duration::from_micros(micros.min(u64::MAX as u128) as u64)
+}
+
+fn arm_itimer(tid: usize, id: u64, delay: Duration) {
+    crate::timer::manager::get().add_timer(
+        delay,
+        Box::new(move || {
+            itimer_expired(tid, id);
+        }),
+    );
+}
+
+fn itimer_expired(tid: usize, id: u64) {
+    let thread = match scheduler::get().threads.get_mut(tid) {
+        Some(Some(thread)) => thread,
+        _ => return,
+    };
+    let itimer = match thread.signal.itimer.as_mut() {
+        Some(itimer) if itimer.id == id => itimer,
+        // The timer was cancelled or replaced
+        _ => return,
+    };
+    if itimer.interval.is_zero() {
+        thread.signal.itimer = None;
+    } else {
+        let tm = crate::timer::manager::get();
+        itimer.expiry = tm
+            .get_current()
+            .saturating_add(tm.to_ticks(itimer.interval));
+        arm_itimer(tid, id, itimer.interval);
+    }
+    send(tid, SIGALRM);
+}
+
+// Arm (or cancel with a zero value) the real-time interval timer of the
+// current thread, returning the time left on the previous one.
+pub fn setitimer(value: Duration, interval: Duration) -> Duration {
+    let sched = scheduler::get();
+    let tid = sched.current.unwrap();
+    let thread = sched.current_thread();
+    let tm = crate::timer::manager::get();
+    let now = tm.get_current();
+    let remaining = match thread.signal.itimer {
+        Some(itimer) => ticks_to_duration(itimer.expiry.saturating_sub(now)),
+        None => Duration::ZERO,
+    };
+    if value.is_zero() {
+        thread.signal.itimer = None;
+        return remaining;
+    }
+    let id = unsafe {
+        NEXT_ITIMER_ID += 1;
+        NEXT_ITIMER_ID
+    };
+    thread.signal.itimer = Some(ITimer {
+        id,
+        // to_ticks saturates, a huge value must not wrap around
+        expiry: now.saturating_add(tm.to_ticks(value)),
+        interval,
+    });
+    arm_itimer(tid, id, value);
+    remaining
+}
+
+pub fn alarm(seconds: u64) -> u64 {
+    let remaining = setitimer(Duration::from_secs(seconds), Duration::ZERO);
+    // Round up like POSIX alarm so a pending alarm never reports 0
+    remaining.as_secs() + (remaining.subsec_nanos() > 0) as u64
+}
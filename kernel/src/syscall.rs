@@ -1,87 +1,529 @@
-use crate::scheduler;
+use crate::exception::trap_frame::TRAP_FRAME;
+use crate::file::initramfs::InitramfsFile;
+use crate::file::pipe::{self, PipeError};
+use crate::file::File;
+use crate::mmu::vm::{MAP_ANONYMOUS, MAP_PRIVATE, MAP_SHARED};
+use crate::oom;
+use crate::scheduler::{self, Wait};
+use crate::signal;
+use crate::timer::{self, idle};
+use abi::sysno::WNOHANG;
+use abi::sysno::{SYSLOG_CLEAR, SYSLOG_CONSOLE_LEVEL, SYSLOG_READ_ALL, SYSLOG_SIZE_BUFFER};
+use abi::types::{ProcInfo, Timespec, CLOCK_MONOTONIC};
+use abi::Errno;
+use alloc::boxed::Box;
 use alloc::string::String;
-use stdio::println;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem::{size_of, size_of_val};
+use core::time::Duration;
+use stdio::debug;
+use stdio::log::{self, Level};
 
 pub fn get_pid() -> u64 {
     scheduler::get().current.unwrap() as u64
 }
 
-pub fn read(buf: *mut u8, size: usize) -> usize {
+// Longest string taken from user memory, with the NUL
+const MAX_STRING: usize = 0x1000;
+// Most arguments exec takes
+const MAX_ARGS: usize = 256;
+
+// The bytes of a plain value, for copying it to or from user memory
+fn bytes_of<T: Copy>(value: &T) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+fn bytes_of_mut<T: Copy>(value: &mut T) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(value as *mut T as *mut u8, size_of::<T>()) }
+}
+
+fn copy_to_user(addr: u64, data: &[u8]) -> Result<(), Errno> {
+    let vm = &scheduler::get().current_thread().vm;
+    vm.copy_to_user(addr, data).ok_or(Errno::EFAULT)
+}
+
+fn copy_from_user(addr: u64, buf: &mut [u8]) -> Result<(), Errno> {
+    let vm = &scheduler::get().current_thread().vm;
+    vm.copy_from_user(addr, buf).ok_or(Errno::EFAULT)
+}
+
+fn put_user<T: Copy>(addr: u64, value: &T) -> Result<(), Errno> {
+    let vm = &scheduler::get().current_thread().vm;
+    vm.put_user(addr, value).ok_or(Errno::EFAULT)
+}
+
+// Only for integers and structs of them, which any bytes are valid for
+fn get_user<T: Copy>(addr: u64) -> Result<T, Errno> {
+    let vm = &scheduler::get().current_thread().vm;
+    unsafe { vm.get_user(addr) }.ok_or(Errno::EFAULT)
+}
+
+// Whether len bytes at addr can be written, checked before anything is
+// done that a failing copy could not undo
+fn writable(addr: u64, len: usize) -> Result<(), Errno> {
+    let vm = &scheduler::get().current_thread().vm;
+    if vm.access_ok(addr, len, true) {
+        Ok(())
+    } else {
+        Err(Errno::EFAULT)
+    }
+}
+
+// Re-execute the current syscall once the thread is woken up
+fn block_and_restart() {
+    unsafe {
+        TRAP_FRAME.as_mut().unwrap().state.pc -= 4;
+    }
+    scheduler::get().block_current();
+}
+
+fn console_read(buf: &mut [u8]) -> usize {
     let mut read = 0;
-    for i in 0..size {
-        unsafe {
-            if let Some(c) = driver::uart::recv_async() {
-                *buf.add(i) = c;
-                read += 1;
-            } else {
-                break;
-            }
+    for c in buf.iter_mut() {
+        if let Some(ch) = driver::uart::recv_async() {
+            *c = ch;
+            read += 1;
+        } else {
+            break;
         }
     }
     read
 }
 
-pub fn write(buf: *const u8, size: usize) -> usize {
-    let mut written = 0;
-    for i in 0..size {
-        unsafe {
-            let c = *buf.add(i);
-            stdio::send(c);
+// Reads and writes move at most this much per call, through a kernel buffer
+const IO_CHUNK: usize = 0x1000;
+
+// Returns None if the thread was switched out and x0 must be left untouched
+pub fn read(fd: usize, buf: u64, size: usize) -> Option<u64> {
+    let tid = scheduler::get().current.unwrap();
+    let mut data = [0; IO_CHUNK];
+    let data = &mut data[..size.min(IO_CHUNK)];
+    // Checked up front, so that nothing is consumed from a pipe for nothing
+    if let Err(errno) = writable(buf, data.len()) {
+        return Some(errno.to_ret());
+    }
+    if data.is_empty() {
+        return Some(0);
+    }
+    let read = match scheduler::get().current_thread().files.get(fd) {
+        Some(File::Console) => {
+            let read = console_read(data);
+            if read == 0 {
+                if scheduler::get().ready_queue.is_empty() {
+                    idle::wait_for_interrupt();
+                }
+                unsafe {
+                    TRAP_FRAME.as_mut().unwrap().state.pc -= 4;
+                }
+                return None;
+            }
+            read
         }
-        written += 1;
+        Some(File::Initramfs(file)) => file.read(data),
+        Some(File::PipeRead(pipe)) => match pipe.read(data) {
+            Ok(read) => read,
+            Err(_) => {
+                pipe.wait(tid);
+                block_and_restart();
+                return None;
+            }
+        },
+        _ => return Some(Errno::EBADF.to_ret()),
+    };
+    match copy_to_user(buf, &data[..read]) {
+        Ok(()) => Some(read as u64),
+        Err(errno) => Some(errno.to_ret()),
     }
-    written
 }
 
-pub fn exec(name: *const u8) -> u64 {
-    let name: String = {
-        let mut ret = String::new();
-        let mut i = 0;
-        loop {
-            unsafe {
-                let c = *name.add(i);
-                if c == 0 {
-                    break;
-                }
-                ret.push(c as char);
+pub fn write(fd: usize, buf: u64, size: usize) -> Option<u64> {
+    let tid = scheduler::get().current.unwrap();
+    let mut data = [0; IO_CHUNK];
+    let data = &mut data[..size.min(IO_CHUNK)];
+    if let Err(errno) = copy_from_user(buf, data) {
+        return Some(errno.to_ret());
+    }
+    match scheduler::get().current_thread().files.get(fd) {
+        Some(File::Console) => {
+            for &c in data.iter() {
+                stdio::send(c);
+            }
+            Some(data.len() as u64)
+        }
+        Some(File::PipeWrite(pipe)) => match pipe.write(data) {
+            Ok(written) => Some(written as u64),
+            Err(PipeError::WouldBlock) => {
+                pipe.wait(tid);
+                block_and_restart();
+                None
+            }
+            Err(PipeError::BrokenPipe) => {
+                signal::send(tid, signal::SIGPIPE);
+                Some(Errno::EPIPE.to_ret())
+            }
+        },
+        _ => Some(Errno::EBADF.to_ret()),
+    }
+}
+
+pub fn pipe(fds: u64) -> u64 {
+    if let Err(errno) = writable(fds, 2 * size_of::<i32>()) {
+        return errno.to_ret();
+    }
+    let files = &mut scheduler::get().current_thread().files;
+    let (reader, writer) = pipe::new();
+    let rfd = match files.insert(File::PipeRead(reader)) {
+        Some(fd) => fd,
+        None => return Errno::EMFILE.to_ret(),
+    };
+    let wfd = match files.insert(File::PipeWrite(writer)) {
+        Some(fd) => fd,
+        None => {
+            files.close(rfd);
+            return Errno::EMFILE.to_ret();
+        }
+    };
+    match put_user(fds, &[rfd as i32, wfd as i32]) {
+        Ok(()) => 0,
+        Err(errno) => errno.to_ret(),
+    }
+}
+
+pub fn close(fd: usize) -> u64 {
+    if scheduler::get().current_thread().files.close(fd) {
+        0
+    } else {
+        Errno::EBADF.to_ret()
+    }
+}
+
+pub fn dup2(oldfd: usize, newfd: usize) -> u64 {
+    let files = &mut scheduler::get().current_thread().files;
+    let file = match files.get(oldfd) {
+        Some(file) => file.clone(),
+        None => return Errno::EBADF.to_ret(),
+    };
+    if oldfd != newfd && !files.set(newfd, file) {
+        return Errno::EBADF.to_ret();
+    }
+    newfd as u64
+}
+
+fn c_string(addr: u64) -> Result<String, Errno> {
+    let vm = &scheduler::get().current_thread().vm;
+    let bytes = vm
+        .strncpy_from_user(addr, MAX_STRING)
+        .ok_or(Errno::EFAULT)?;
+    Ok(bytes.iter().map(|&c| c as char).collect())
+}
+
+pub fn open(path: u64) -> u64 {
+    let path = match c_string(path) {
+        Ok(path) => path,
+        Err(errno) => return errno.to_ret(),
+    };
+    let file = match InitramfsFile::open(path.trim_start_matches('/')) {
+        Some(file) => file,
+        None => return Errno::ENOENT.to_ret(),
+    };
+    match scheduler::get()
+        .current_thread()
+        .files
+        .insert(File::Initramfs(file))
+    {
+        Some(fd) => fd as u64,
+        None => Errno::EMFILE.to_ret(),
+    }
+}
+
+pub fn mmap(addr: u64, len: usize, prot: u64, flags: u64, fd: usize, offset: usize) -> u64 {
+    if flags & (MAP_SHARED | MAP_PRIVATE) == 0 || offset % 0x1000 != 0 {
+        return Errno::EINVAL.to_ret();
+    }
+    if !oom::can_allocate(len as u64) {
+        return Errno::ENOMEM.to_ret();
+    }
+    let thread = scheduler::get().current_thread();
+    let data = if flags & MAP_ANONYMOUS != 0 {
+        None
+    } else {
+        match thread.files.get(fd) {
+            Some(File::Initramfs(file)) => match file.data().get(offset..) {
+                Some(data) => Some(data),
+                None => return Errno::EINVAL.to_ret(),
+            },
+            Some(_) => return Errno::ENODEV.to_ret(),
+            None => return Errno::EBADF.to_ret(),
+        }
+    };
+    match thread.vm.mmap_area(addr, len, prot, flags, data) {
+        Some(addr) => addr,
+        None => Errno::ENOMEM.to_ret(),
+    }
+}
+
+pub fn munmap(addr: u64, len: usize) -> u64 {
+    if scheduler::get().current_thread().vm.munmap(addr, len) {
+        0
+    } else {
+        Errno::EINVAL.to_ret()
+    }
+}
+
+pub fn mprotect(addr: u64, len: usize, prot: u64) -> u64 {
+    if scheduler::get()
+        .current_thread()
+        .vm
+        .mprotect(addr, len, prot)
+    {
+        0
+    } else {
+        Errno::ENOMEM.to_ret()
+    }
+}
+
+// Returns the new program break, or the current one if it cannot be moved
+pub fn brk(addr: u64) -> u64 {
+    let thread = scheduler::get().current_thread();
+    thread.set_brk(addr).unwrap_or(thread.brk)
+}
+
+pub fn sbrk(increment: i64) -> u64 {
+    let thread = scheduler::get().current_thread();
+    let old = thread.brk;
+    match thread.set_brk(old.wrapping_add(increment as u64)) {
+        Some(_) => old,
+        None => Errno::ENOMEM.to_ret(),
+    }
+}
+
+// argv is a null terminated array of user pointers, the program name alone is used if it is null.
+// A stack_size of 0 gives the program the default stack.
+pub fn exec(name: u64, argv: u64, stack_size: u64) -> Option<u64> {
+    let ret = exec_args(name, argv).and_then(|(name, args)| {
+        debug!("exec: {}", name);
+        scheduler::get().exec(name, args, stack_size as usize)
+    });
+    match ret {
+        // The trap frame now belongs to the new program
+        Ok(()) => None,
+        Err(errno) => Some(errno.to_ret()),
+    }
+}
+
+// The program name and the arguments taken from user memory
+fn exec_args(name: u64, argv: u64) -> Result<(String, Vec<String>), Errno> {
+    let name = c_string(name)?;
+    let mut args = Vec::new();
+    if argv == 0 {
+        args.push(name.clone());
+        return Ok((name, args));
+    }
+    let mut ptr = argv;
+    loop {
+        let arg: u64 = get_user(ptr)?;
+        if arg == 0 {
+            return Ok((name, args));
+        }
+        if args.len() == MAX_ARGS {
+            return Err(Errno::E2BIG);
+        }
+        args.push(c_string(arg)?);
+        ptr += 8;
+    }
+}
+
+// A negative pid waits for any child, the exit status is stored if status is not null
+pub fn wait(pid: i64, status: u64, options: u64) -> Option<u64> {
+    let pid = if pid < 0 { None } else { Some(pid as usize) };
+    // The child is gone once collected, its status must have somewhere to go
+    if status != 0 {
+        if let Err(errno) = writable(status, size_of::<u64>()) {
+            return Some(errno.to_ret());
+        }
+    }
+    match scheduler::get().wait(pid) {
+        Wait::Exited(tid, exit_status) => {
+            if status != 0 {
+                let _ = put_user(status, &exit_status);
+            }
+            Some(tid as u64)
+        }
+        Wait::Running if options & WNOHANG != 0 => Some(0),
+        Wait::Running => {
+            block_and_restart();
+            None
+        }
+        Wait::NoChild => Some(Errno::ECHILD.to_ret()),
+    }
+}
+
+// Fill up to count entries of buf, returning the number of processes
+pub fn procinfo(buf: u64, count: usize) -> u64 {
+    let sched = scheduler::get();
+    let infos = sched.procinfo();
+    for (i, info) in infos.iter().take(count).enumerate() {
+        let addr = buf + (i * size_of::<ProcInfo>()) as u64;
+        if let Err(errno) = put_user(addr, info) {
+            return errno.to_ret();
+        }
+    }
+    infos.len() as u64
+}
+
+// The sleep is restarted after any wakeup before the deadline, a signal
+// with a handler ends it early with EINTR instead (see signal::do_signal)
+pub fn nanosleep(req: u64) -> Option<u64> {
+    let tid = scheduler::get().current.unwrap();
+    let thread = scheduler::get().current_thread();
+    let tm = timer::manager::get();
+    let deadline = match thread.sleep_until {
+        Some(deadline) => deadline,
+        None => {
+            let req: Timespec = match get_user(req) {
+                Ok(req) => req,
+                Err(errno) => return Some(errno.to_ret()),
+            };
+            if req.sec < 0 || !(0..1_000_000_000).contains(&req.nsec) {
+                return Some(Errno::EINVAL.to_ret());
             }
-            i += 1;
+            let duration = Duration::new(req.sec as u64, req.nsec as u32);
+            if duration.is_zero() {
+                return Some(0);
+            }
+            let deadline = tm.add_timer(duration, Box::new(move || wake_sleeper(tid)));
+            thread.sleep_until = Some(deadline);
+            deadline
+        }
+    };
+    if tm.get_current() >= deadline {
+        thread.sleep_until = None;
+        return Some(0);
+    }
+    block_and_restart();
+    None
+}
+
+// The timer of a sleep cut short by a signal may still fire during a later
+// block of the thread, so only a sleep that is due is woken
+fn wake_sleeper(tid: usize) {
+    let sched = scheduler::get();
+    let now = timer::manager::get().get_current();
+    if let Some(Some(thread)) = sched.threads.get(tid) {
+        if thread.sleep_until.is_some_and(|deadline| deadline <= now) {
+            sched.wake(tid);
         }
-        ret
+    }
+}
+
+pub fn clock_gettime(clock: u64, tp: u64) -> u64 {
+    if clock != CLOCK_MONOTONIC {
+        return Errno::EINVAL.to_ret();
+    }
+    let tm = timer::manager::get();
+    let now = tm.get_current();
+    let freq = tm.get_frequency();
+    let now = Timespec {
+        sec: (now / freq) as i64,
+        nsec: ((now % freq) * 1_000_000_000 / freq) as i64,
     };
-    println!("exec: {}", name);
-    scheduler::get().exec(name);
-    0
+    match put_user(tp, &now) {
+        Ok(()) => 0,
+        Err(errno) => errno.to_ret(),
+    }
+}
+
+// arg is the buffer length for SYSLOG_READ_ALL and the level for SYSLOG_CONSOLE_LEVEL
+pub fn syslog(action: u64, buf: u64, arg: u64) -> u64 {
+    match action {
+        SYSLOG_READ_ALL => {
+            let mut data = vec![0; (arg as usize).min(log::LOG_BUF_SIZE)];
+            let len = log::read(&mut data);
+            match copy_to_user(buf, &data[..len]) {
+                Ok(()) => len as u64,
+                Err(errno) => errno.to_ret(),
+            }
+        }
+        SYSLOG_CLEAR => {
+            log::clear();
+            0
+        }
+        SYSLOG_CONSOLE_LEVEL => match Level::ALL.iter().find(|level| **level as u64 == arg) {
+            Some(level) => {
+                log::set_console_level(*level);
+                0
+            }
+            None => Errno::EINVAL.to_ret(),
+        },
+        SYSLOG_SIZE_BUFFER => log::LOG_BUF_SIZE as u64,
+        _ => Errno::EINVAL.to_ret(),
+    }
 }
 
 pub fn fork() -> u64 {
-    scheduler::get().fork()
+    scheduler::get().fork().unwrap_or(Errno::ENOMEM.to_ret())
 }
 
 pub fn exit(status: u64) {
-    println!("exit: {}", status);
     scheduler::get().exit(status);
 }
 
-pub fn mbox_call(channel: u8, mbox: *mut u32) -> u64 {
-    let mut mailbox = [0; 36];
-    let len = unsafe { *mbox } as usize / 4;
-    for i in 0..len {
-        unsafe {
-            mailbox[i] = *mbox.add(i);
-        }
+// The buffer starts with its size in bytes, at most that of the kernel's
+pub fn mbox_call(channel: u8, mbox: u64) -> u64 {
+    let mut buf = [0u32; 36];
+    let len = match get_user::<u32>(mbox) {
+        Ok(len) if (4..=size_of_val(&buf) as u32).contains(&len) => len as usize,
+        Ok(_) => return Errno::EINVAL.to_ret(),
+        Err(errno) => return errno.to_ret(),
+    };
+    if let Err(errno) = copy_from_user(mbox, &mut bytes_of_mut(&mut buf)[..len]) {
+        return errno.to_ret();
     }
-    let mut mailbox = driver::mailbox::MailBox::new(&mailbox);
+    let mut mailbox = driver::mailbox::MailBox::new(&buf);
     let ret = mailbox.call(channel);
 
-    for i in 0..mailbox.get(0) as usize / 4 {
-        unsafe {
-            *mbox.add(i) = mailbox.get(i as usize);
-        }
+    for (i, word) in buf.iter_mut().enumerate().take(len / 4) {
+        *word = mailbox.get(i);
+    }
+    match copy_to_user(mbox, &bytes_of(&buf)[..len]) {
+        Ok(()) => ret as u64,
+        Err(errno) => errno.to_ret(),
+    }
+}
+
+pub fn kill(pid: u64) -> u64 {
+    signal_kill(pid, signal::SIGKILL as u64)
+}
+
+pub fn sigaction(sig: u64, handler: u64) -> u64 {
+    signal::sigaction(sig as usize, handler).unwrap_or(Errno::EINVAL.to_ret())
+}
+
+pub fn signal_kill(pid: u64, sig: u64) -> u64 {
+    if signal::send(pid as usize, sig as usize) {
+        0
+    } else {
+        Errno::ESRCH.to_ret()
     }
-    ret as u64
 }
 
-pub fn kill(pid: u64) {
-    scheduler::get().kill(pid as usize);
+pub fn sigprocmask(how: u64, set: u64) -> u64 {
+    signal::sigprocmask(how, set).unwrap_or(Errno::EINVAL.to_ret())
+}
+
+pub fn alarm(seconds: u64) -> u64 {
+    signal::alarm(seconds)
+}
+
+pub fn setitimer(value_ms: u64, interval_ms: u64) -> u64 {
+    let remaining = signal::setitimer(
+        Duration::from_millis(value_ms),
+        Duration::from_millis(interval_ms),
+    );
+    remaining.as_millis() as u64
+}
+
+pub fn sigreturn() {
+    signal::sigreturn();
 }
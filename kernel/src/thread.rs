@@ -1,11 +1,16 @@
 pub mod cpu;
 pub mod state;
 
+use crate::file::FileTable;
 use crate::mmu::config::GPU_CONFIG;
-use crate::mmu::config::STACK_CONFIG;
-use crate::mmu::config::TEXT_CONFIG;
+use crate::mmu::config::GUARD_SIZE;
 use crate::mmu::vm::VirtualMemory;
-use stdio::println;
+use crate::mmu::vm::{PROT_EXEC, PROT_READ, PROT_WRITE};
+use crate::oom;
+use crate::signal;
+use alloc::string::String;
+use alloc::vec::Vec;
+use stdio::trace;
 
 #[repr(C)]
 #[derive(Debug)]
@@ -14,74 +19,181 @@ pub struct Thread {
     pub state: state::State,
     pub stack: *mut u8,
     pub stack_size: usize,
-    pub entry: *mut u8,
-    pub len: usize,
     pub cpu_state: cpu::State,
     pub vm: VirtualMemory,
+    pub signal: signal::SignalState,
+    pub files: FileTable,
+    pub heap_start: u64,
+    pub brk: u64,
+    pub parent: Option<usize>,
+    pub exit_status: u64,
+    // The tick a nanosleep in progress ends at
+    pub sleep_until: Option<u64>,
+    pub name: String,
+    pub stats: Stats,
+}
+
+// Scheduler accounting, reported through procinfo
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stats {
+    // Timer ticks spent running
+    pub cpu_ticks: u64,
+    // Times the thread was switched in
+    pub switches: u64,
+}
+
+// The heap grows from the page after the program image up to the GPU mapping
+const HEAP_END: u64 = 0x3C00_0000;
+
+// The user stack grows down from here. Nothing is mapped in the page below
+// it, so that an overflow faults instead of running into other memory.
+const STACK_TOP: u64 = 0xffff_ffff_f000;
+pub const MAX_STACK_SIZE: usize = 0x100_0000;
+
+// Neither the largest stack nor its guard page reach the trampoline
+const _: () =
+    assert!(signal::SIGRETURN_ADDR + 0x1000 <= STACK_TOP - MAX_STACK_SIZE as u64 - GUARD_SIZE);
+
+fn page_align_up(addr: u64) -> u64 {
+    (addr + 0xfff) & !0xfff
+}
+
+// Map the sigreturn trampoline that signal handlers return into
+fn map_trampoline(vm: &mut VirtualMemory) -> Option<()> {
+    vm.map_image(
+        signal::SIGRETURN_ADDR,
+        signal::trampoline(),
+        PROT_READ | PROT_EXEC,
+    )
 }
 
 impl Thread {
-    pub fn new(stack_size: usize, entry: *mut u8, len: usize) -> Self {
-        let mut vm = VirtualMemory::new();
-        let stack = vm.mmap(0xffff_ffff_b000, stack_size, STACK_CONFIG) as *mut u8;
-        println!(
-            "Stack: {:x}-{:x}",
-            stack as usize,
-            stack as usize + stack_size
-        );
-        let pc = vm.map_pa(0x0000_0000_0000, entry as u64, len, TEXT_CONFIG);
-        vm.map_pa(0x3C00_0000, 0x3C00_0000, 0x400_0000, GPU_CONFIG);
-        assert!(stack == 0xffff_ffff_b000 as *mut u8);
+    // Map a private copy of image straight from the initramfs, None when out
+    // of memory
+    pub fn new(stack_size: usize, image: &[u8]) -> Option<Self> {
+        let mut vm = VirtualMemory::new()?;
+        let stack = (STACK_TOP - stack_size as u64) as *mut u8;
+        vm.map_zeroed(stack as u64, stack_size, PROT_READ | PROT_WRITE)?;
+        vm.map_image(0x0000_0000_0000, image, PROT_READ | PROT_WRITE | PROT_EXEC)?;
+        let pc = 0x0000_0000_0000 as *mut u8;
+        vm.map_pa(0x3C00_0000, 0x3C00_0000, 0x400_0000, GPU_CONFIG)?;
+        map_trampoline(&mut vm)?;
         let cpu_state = cpu::State::new(stack, stack_size, pc, vm.get_l0_addr());
-        println!(
-            "Stack: {:x}-{:x}",
+        trace!(
+            "Stack: {:x}-{:x}, pc: {:x}",
             stack as usize,
-            stack as usize + stack_size
+            stack as usize + stack_size,
+            pc as usize
         );
-        println!("pc: {:x}", pc as usize);
-        Thread {
+        let heap_start = page_align_up(image.len() as u64);
+        Some(Thread {
             id: 0xC8763,
             state: state::State::Ready,
             stack,
             stack_size,
-            entry,
-            len,
             cpu_state,
             vm,
+            signal: signal::SignalState::new(),
+            files: FileTable::new(),
+            heap_start,
+            brk: heap_start,
+            parent: None,
+            exit_status: 0,
+            sleep_until: None,
+            name: String::new(),
+            stats: Stats::default(),
+        })
+    }
+
+    // Copy the arguments to the top of the stack and pass argc/argv in x0/x1,
+    // None when they do not fit on the stack
+    pub fn set_args(&mut self, args: &[String]) -> Option<()> {
+        if let Some(name) = args.first() {
+            self.name = name.clone();
+        }
+        let bottom = self.stack as u64;
+        let mut sp = bottom + self.stack_size as u64;
+        let mut argv = Vec::new();
+        for arg in args.iter() {
+            sp = sp
+                .checked_sub(arg.len() as u64 + 1)
+                .filter(|&sp| sp >= bottom)?;
+            self.vm.copy_to_user(sp, arg.as_bytes())?;
+            self.vm.put_user(sp + arg.len() as u64, &0u8)?;
+            argv.push(sp);
+        }
+        argv.push(0);
+        sp = sp
+            .checked_sub(argv.len() as u64 * 8)
+            .map(|sp| sp & !0xf)
+            .filter(|&sp| sp >= bottom)?;
+        for (i, ptr) in argv.iter().enumerate() {
+            self.vm.put_user(sp + i as u64 * 8, ptr)?;
         }
+        self.cpu_state.x[0] = args.len() as u64;
+        self.cpu_state.x[1] = sp;
+        self.cpu_state.sp = sp;
+        Some(())
     }
-}
 
-impl Clone for Thread {
-    fn clone(&self) -> Self {
-        let mut vm = VirtualMemory::new();
-        let stack = vm.mmap(0xffff_ffff_b000, self.stack_size, STACK_CONFIG) as *mut u8;
-        unsafe {
-            core::ptr::copy_nonoverlapping(
-                self.vm.get_phys(self.stack as u64),
-                vm.get_phys(stack as u64),
-                self.stack_size,
-            );
+    pub fn set_brk(&mut self, brk: u64) -> Option<u64> {
+        if brk < self.heap_start || brk > HEAP_END {
+            return None;
+        }
+        let old_end = page_align_up(self.brk);
+        let new_end = page_align_up(brk);
+        if new_end > old_end {
+            if !oom::can_allocate(new_end - old_end) {
+                return None;
+            }
+            self.vm.map_zeroed(
+                old_end,
+                (new_end - old_end) as usize,
+                PROT_READ | PROT_WRITE,
+            )?;
+        } else if new_end < old_end {
+            self.vm.munmap(new_end, (old_end - new_end) as usize);
         }
+        self.brk = brk;
+        Some(brk)
+    }
+
+    // Whether addr is in the guard page below the stack
+    pub fn in_stack_guard(&self, addr: u64) -> bool {
+        let bottom = self.stack as u64;
+        (bottom - GUARD_SIZE..bottom).contains(&addr)
+    }
+
+    // Bytes of memory mapped for the program, heap, mmap areas and stack
+    pub fn memory_usage(&self) -> u64 {
+        self.vm.mapped_size()
+    }
+}
+
+impl Thread {
+    // The child of a fork, None when out of memory
+    pub fn try_clone(&self) -> Option<Self> {
+        let mut vm = VirtualMemory::new()?;
         let mut cpu_state = self.cpu_state.clone();
         cpu_state.l0 = vm.get_l0_addr() as u64;
-        vm.map_pa(0x0000_0000_0000, self.entry as u64, self.len, TEXT_CONFIG);
-        vm.map_pa(0x3C00_0000, 0x3C00_0000, 0x400_0000, GPU_CONFIG);
-        println!(
-            "Cloning thread 0x{:x} to 0x{:x}",
-            self.id, 0xdeadbeaf as u32
+        vm.map_pa(0x3C00_0000, 0x3C00_0000, 0x400_0000, GPU_CONFIG)?;
+        // The stack and the trampoline are areas like any other
+        self.vm.fork_areas(&mut vm)?;
+        trace!(
+            "Cloning thread {}, stack: {:x}-{:x}",
+            self.id,
+            self.stack as usize,
+            self.stack as usize + self.stack_size
         );
-        println!(
-            "Stack: {:x}-{:x}",
-            stack as usize,
-            stack as usize + self.stack_size
-        );
-        Thread {
+        Some(Thread {
             id: 0xdeadbeaf,
-            stack,
             cpu_state,
             vm,
+            signal: self.signal.fork(),
+            files: self.files.clone(),
+            name: self.name.clone(),
+            stats: Stats::default(),
             ..*self
-        }
+        })
     }
 }
@@ -1,4 +1,8 @@
 #[derive(Clone, Copy, Debug)]
 pub enum State {
     Ready,
+    Stopped,
+    Blocked,
+    // Exited, waiting for the parent to collect the status
+    Zombie,
 }
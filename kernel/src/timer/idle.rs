@@ -0,0 +1,36 @@
+use core::arch::asm;
+use core::ptr::addr_of_mut;
+
+#[derive(Clone, Copy, Debug)]
+pub struct IdleStats {
+    pub idle_ticks: u64,
+    pub wakeups: u64,
+    pub ticks_fired: u64,
+    pub ticks_stopped: u64,
+}
+
+static mut IDLE_STATS: IdleStats = IdleStats {
+    idle_ticks: 0,
+    wakeups: 0,
+    ticks_fired: 0,
+    ticks_stopped: 0,
+};
+
+pub fn get() -> &'static mut IdleStats {
+    unsafe { &mut *addr_of_mut!(IDLE_STATS) }
+}
+
+// Sleep until the next interrupt is pending. The timer compare register
+// already holds the next real deadline of the TimerManager.
+// Interrupts are masked inside exception handlers, but `wfi` still wakes up
+// on a pending interrupt, which is then taken right after `eret`.
+pub fn wait_for_interrupt() {
+    let tm = super::manager::get();
+    let start = tm.get_current();
+    unsafe {
+        asm!("dsb sy", "wfi");
+    }
+    let stats = get();
+    stats.idle_ticks += tm.get_current() - start;
+    stats.wakeups += 1;
+}
@@ -1,4 +1,5 @@
 use super::timer::Timer;
+use crate::device::local_intc;
 use alloc::boxed::Box;
 use alloc::collections::BinaryHeap;
 use core::{arch::asm, time::Duration};
@@ -20,7 +21,7 @@ pub fn init() {
     tm.add_timer(
         Duration::from_days(1000),
         Box::new(|| {
-            println!("First boot timer expired!");
+            info!("First boot timer expired!");
         }),
     );
 }
@@ -31,27 +32,14 @@ pub fn get() -> &'static mut TimerManager {
 
 unsafe fn enable_timer_irq() {
     // debug!("Enable timer interrupt");
-    asm!(
-        "mov {0}, 1",
-        "msr cntp_ctl_el0, {0}",
-        "mov {0}, 2",
-        "ldr {1}, =0x40000040",
-        "str {0}, [{1}]",
-        out(reg) _,
-        out(reg) _,
-    );
+    asm!("mov {0}, 1", "msr cntp_ctl_el0, {0}", out(reg) _);
+    local_intc::set_core0_timer_irqs(local_intc::CNTPNSIRQ);
 }
 
 unsafe fn disable_timer_irq() {
     // debug!("Disable timer interrupt");
-    asm!(
-        "mov {0}, 0",
-        "msr cntp_ctl_el0, {0}",
-        "ldr {1}, =0x40000040",
-        "str {0}, [{1}]",
-        out(reg) _,
-        out(reg) _,
-    );
+    asm!("msr cntp_ctl_el0, xzr");
+    local_intc::set_core0_timer_irqs(0);
 }
 
 impl TimerManager {
@@ -62,7 +50,8 @@ impl TimerManager {
         return ret;
     }
 
-    pub fn add_timer(&mut self, duration: Duration, callback: Box<dyn Fn() + Send + Sync>) {
+    // Returns the tick the callback runs at, or later
+    pub fn add_timer(&mut self, duration: Duration, callback: Box<dyn Fn() + Send + Sync>) -> u64 {
         let expiry = self.compute_delay(duration);
         let timer = Timer::new(expiry, callback);
         self.pq.push(timer);
@@ -72,6 +61,7 @@ impl TimerManager {
                 enable_timer_irq();
             }
         }
+        expiry
     }
 
     pub fn handle_interrupt(&mut self) {
@@ -104,10 +94,14 @@ impl TimerManager {
                 );
             }
         } else {
-            println!("No timer to set")
+            warn!("No timer to set")
         }
     }
 
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.pq.peek().map(|timer| timer.expiry)
+    }
+
     pub fn get_current(&self) -> u64 {
         let mut now: u64;
         unsafe {
@@ -130,6 +124,10 @@ impl TimerManager {
         freq
     }
 
+    pub fn to_ticks(&self, duration: Duration) -> u64 {
+        (duration.as_secs_f64() * self.get_frequency() as f64) as u64
+    }
+
     fn compute_delay(&self, duration: Duration) -> u64 {
         let now = self.get_current() as f64;
         let freq = self.get_frequency() as f64;
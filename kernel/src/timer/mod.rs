@@ -1,2 +1,3 @@
+pub mod idle;
 pub mod manager;
 pub mod timer;
@@ -1,12 +1,12 @@
 #![no_std]
-#![feature(start)]
-
-mod panic;
-mod stdio;
-mod syscall;
+#![no_main]
+extern crate alloc;
 
 use core::arch::asm;
-use stdio::{print, print_dec, print_hex, print_u64, println};
+use ulib::abi::signal::{SIGALRM, SIGUSR1};
+use ulib::env::Args;
+use ulib::syscall::{self, MAP_ANONYMOUS, MAP_PRIVATE, MAP_SHARED, PROT_READ, PROT_WRITE};
+use ulib::{print, println};
 
 fn delay(n: u64) {
     unsafe {
@@ -14,11 +14,7 @@ fn delay(n: u64) {
         asm!("mrs {}, cntfrq_el0", out(reg) frq);
         let pct: u64;
         asm!("mrs {}, cntpct_el0", out(reg) pct);
-        print("frq=");
-        print_hex(frq);
-        print(", pct=");
-        print_hex(pct);
-        println("");
+        println!("frq={:x}, pct={:x}", frq, pct);
 
         let mut cur: u64 = 0;
         while pct + n * frq / 1000 > cur {
@@ -26,35 +22,30 @@ fn delay(n: u64) {
         }
     }
 }
-#[start]
-fn main(_: isize, _: *const *const u8) -> isize {
+
+#[no_mangle]
+fn main(args: Args) -> i32 {
+    for (i, arg) in args.enumerate() {
+        println!("argv[{}] = {}", i, arg);
+    }
     basic_test();
-    syscall::exit(0);
-    return 0;
+    0
 }
 
 #[allow(dead_code)]
 fn basic_test() {
-    // println("Hello, world!");
-    let pid = syscall::get_pid();
-    print("PID=");
-    print_hex(pid);
-    println("");
+    // println!("Hello, world!");
+    let pid = syscall::get_pid().unwrap();
+    println!("PID={:x}", pid);
 }
 
 #[allow(dead_code)]
 fn mailbox_test() {
-    println("Printing mailbox info...");
+    println!("Printing mailbox info...");
     let revision = get_board_revision();
-    print("Board revision: ");
-    print_hex(revision);
-    println("");
+    println!("Board revision: {:x}", revision);
     let (lb, ub) = get_arm_memory();
-    print("ARM memory: ");
-    print_hex(lb);
-    print(" - ");
-    print_hex(ub);
-    println("");
+    println!("ARM memory: {:x} - {:x}", lb, ub);
     delay(100);
 }
 
@@ -89,89 +80,161 @@ pub fn get_arm_memory() -> (u64, u64) {
 
 #[allow(dead_code)]
 fn fork_bump() {
-    let pid = syscall::fork();
+    let pid = syscall::fork().unwrap();
     if pid == 0 {
-        println("I'm the child");
+        println!("I'm the child");
     } else {
-        println("I'm the parent");
+        println!("I'm the parent");
     }
 }
 
 #[allow(dead_code)]
 fn thread_test() {
-    println("Thread Test");
+    println!("Thread Test");
     for i in 0..10000000 {
-        let pid = syscall::get_pid();
-        print_u64("PID", pid);
-        print("  ");
-        print_u64("i", i);
-        println("");
+        let pid = syscall::get_pid().unwrap();
+        println!("PID: {} (0x{:x})  i: {} (0x{:x})", pid, pid, i, i);
         delay(1);
     }
 }
 
+fn sp() -> u64 {
+    let sp: u64;
+    unsafe {
+        asm!("mov {}, sp", out(reg) sp);
+    }
+    sp
+}
+
 #[allow(dead_code)]
 fn fork_test() {
     let mut cnt = 0;
-    println("[program] Hello, world!");
-    let pid = syscall::get_pid();
-    print("[program] PID=");
-    print_hex(pid);
-    println("");
-    let child_pid = syscall::fork();
+    println!("[program] Hello, world!");
+    println!("[program] PID={:x}", syscall::get_pid().unwrap());
+    let child_pid = syscall::fork().unwrap();
     if child_pid == 0 {
-        println("[Child] I'm the child");
-        let sp: u64;
-        unsafe {
-            asm!("mov {}, sp", out(reg) sp);
-        }
-        print("[Child] SP=");
-        print_hex(sp);
-        println("");
-        let child_pid2 = syscall::fork();
+        println!("[Child] I'm the child");
+        println!("[Child] SP={:x}", sp());
+        let child_pid2 = syscall::fork().unwrap();
         if child_pid2 != 0 {
-            println("[Child] I'm the parent of the second child");
-            let sp: u64;
-            unsafe {
-                asm!("mov {}, sp", out(reg) sp);
-            }
-            print("[Child] SP=");
-            print_hex(sp);
-            print(", child PID=");
-            print_hex(child_pid2);
-            print(", cnt_ptr=0x");
-            print_hex(&mut cnt as *mut _ as u64);
-            println("");
+            println!("[Child] I'm the parent of the second child");
+            println!(
+                "[Child] SP={:x}, child PID={:x}, cnt_ptr=0x{:x}",
+                sp(),
+                child_pid2,
+                &mut cnt as *mut _ as u64
+            );
         } else {
-            println("[Child2] I'm the second child");
+            println!("[Child2] I'm the second child");
             while cnt < 10 {
-                let sp: u64;
-                unsafe {
-                    asm!("mov {}, sp", out(reg) sp);
-                }
-                print("[Child2] SP=");
-                print_hex(sp);
-                print(", cnt=");
-                print_dec(cnt);
-                print(", cnt_ptr=0x");
-                print_hex(&mut cnt as *mut _ as u64);
-                println("");
+                let cnt_ptr = &mut cnt as *mut _ as u64;
+                println!(
+                    "[Child2] SP={:x}, cnt={}, cnt_ptr=0x{:x}",
+                    sp(),
+                    cnt,
+                    cnt_ptr
+                );
                 cnt += 1;
             }
         }
     } else {
-        println("[Parent] I'm the parent");
-        let sp: u64;
-        unsafe {
-            asm!("mov {}, sp", out(reg) sp);
-        }
+        println!("[Parent] I'm the parent");
+        let sp = sp();
         delay(10000000000);
-        print("[Parent] SP=");
-        print_hex(sp);
-        print(", child PID=");
-        print_hex(child_pid);
-        println("");
+        println!("[Parent] SP={:x}, child PID={:x}", sp, child_pid);
+    }
+}
+
+#[allow(dead_code)]
+fn alarm_handler(sig: u64) {
+    println!("[Signal] Caught signal {}", sig);
+}
+
+#[allow(dead_code)]
+fn signal_test() {
+    syscall::sigaction(SIGALRM, alarm_handler).unwrap();
+    syscall::sigaction(SIGUSR1, alarm_handler).unwrap();
+    let pid = syscall::get_pid().unwrap();
+    syscall::signal_kill(pid, SIGUSR1).unwrap();
+    syscall::setitimer(500, 500).unwrap();
+    for i in 0..5 {
+        println!("tick: {} (0x{:x})", i, i);
+        delay(1000);
+    }
+    syscall::setitimer(0, 0).unwrap();
+    syscall::alarm(1).unwrap();
+    delay(2000);
+}
+
+#[allow(dead_code)]
+fn pipe_test() {
+    let [rfd, wfd] = syscall::pipe().unwrap();
+    if syscall::fork().unwrap() == 0 {
+        syscall::close(rfd).unwrap();
+        syscall::write(wfd, b"Hello through the pipe!").unwrap();
+        syscall::exit(0);
+    } else {
+        syscall::close(wfd).unwrap();
+        let mut buf = [0u8; 64];
+        while let Ok(n @ 1..) = syscall::read(rfd, &mut buf) {
+            println!(
+                "[Parent] Read: {}",
+                core::str::from_utf8(&buf[..n]).unwrap_or("?")
+            );
+        }
+        println!("[Parent] EOF");
+    }
+}
+
+#[allow(dead_code)]
+fn mmap_test() {
+    let shared = syscall::mmap(
+        0,
+        0x1000,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED | MAP_ANONYMOUS,
+        -1,
+        0,
+    )
+    .unwrap();
+    println!("[mmap] shared={:x}", shared as u64);
+    let value = shared as *mut u64;
+    unsafe { *value = 0 };
+    if syscall::fork().unwrap() == 0 {
+        unsafe { *value = 0xdead_beef };
+        syscall::exit(0);
+    }
+    delay(1000);
+    println!("[mmap] value written by child={:x}", unsafe { *value });
+
+    let fd = syscall::open("file1.txt").unwrap();
+    let file = syscall::mmap(0, 0x1000, PROT_READ, MAP_PRIVATE, fd, 0).unwrap();
+    println!("[mmap] file1.txt: {}", unsafe {
+        core::str::from_utf8_unchecked(core::slice::from_raw_parts(file, 17))
+    });
+    syscall::mprotect(shared, 0x1000, PROT_READ).unwrap();
+    syscall::munmap(file, 0x1000).unwrap();
+    syscall::munmap(shared, 0x1000).unwrap();
+}
+
+#[allow(dead_code)]
+fn alloc_test() {
+    use alloc::boxed::Box;
+    use alloc::string::String;
+    use alloc::vec::Vec;
+
+    let mut v = Vec::new();
+    for i in 0..100u64 {
+        v.push(i * i);
     }
+    println!("[alloc] sum of squares: {}", v.iter().sum::<u64>());
+    let mut s = String::from("Hello");
+    s.push_str(" from the user heap!");
+    println!("{}", s);
+    let b = Box::new([0u8; 0x2000]);
+    println!("[alloc] box at 0x{:x}", b.as_ptr() as u64);
+    print!("[alloc] brk=0x{:x}", syscall::brk(0).unwrap());
+    println!();
 }
 
 #[repr(C, align(16))]
@@ -194,7 +257,7 @@ impl MailBox {
     }
 
     pub fn call(&mut self, channel: u8) -> bool {
-        syscall::mbox_call(channel, self.buffer.as_mut_ptr()) == 1
+        syscall::mbox_call(channel, &mut self.buffer).unwrap_or(false)
     }
 
     pub fn get(&self, index: usize) -> u64 {
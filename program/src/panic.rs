@@ -1,11 +0,0 @@
-#![cfg(not(test))]
-
-use core::arch::asm;
-use core::panic::PanicInfo;
-
-#[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    loop {
-        unsafe { asm!("b .") }
-    }
-}
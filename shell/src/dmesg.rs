@@ -0,0 +1,50 @@
+use alloc::string::String;
+use alloc::vec;
+use ulib::println;
+use ulib::syscall::{self, SYSLOG_CLEAR, SYSLOG_READ_ALL, SYSLOG_SIZE_BUFFER};
+
+fn print_log() -> syscall::Result<()> {
+    let size = syscall::syslog(SYSLOG_SIZE_BUFFER, &mut [])?;
+    let mut buf = vec![0; size];
+    let len = syscall::syslog(SYSLOG_READ_ALL, &mut buf)?;
+    for line in String::from_utf8_lossy(&buf[..len]).lines() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+// dmesg [-c] [-n level]
+pub fn dmesg(argv: &[&str]) {
+    let mut clear = false;
+    let mut level = None;
+    let mut args = argv[1..].iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "-c" => clear = true,
+            "-n" => match args.next().and_then(|n| n.parse::<u64>().ok()) {
+                Some(n) => level = Some(n),
+                None => {
+                    println!("Usage: dmesg [-c] [-n level]");
+                    return;
+                }
+            },
+            _ => {
+                println!("Usage: dmesg [-c] [-n level]");
+                return;
+            }
+        }
+    }
+    if let Some(level) = level {
+        if let Err(err) = syscall::set_console_loglevel(level) {
+            println!("dmesg: {}", err);
+        }
+        return;
+    }
+    if let Err(err) = print_log() {
+        println!("dmesg: {}", err);
+        return;
+    }
+    if clear {
+        let _ = syscall::syslog(SYSLOG_CLEAR, &mut []);
+    }
+}
@@ -0,0 +1,171 @@
+#![no_std]
+#![no_main]
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use ulib::env::Args;
+use ulib::io::{stdout, STDIN, STDOUT};
+use ulib::process::EXEC_FAILED;
+use ulib::syscall;
+use ulib::{print, println};
+
+mod dmesg;
+mod ps;
+
+const MAX_LINE_LEN: usize = 0x100;
+
+// Read a line from stdin, echoing it back. Returns false at end of file.
+fn read_line(line: &mut String) -> bool {
+    line.clear();
+    loop {
+        let mut c = [0u8; 1];
+        match syscall::read(STDIN, &mut c) {
+            Ok(0) | Err(_) => return false,
+            Ok(_) => {}
+        }
+        match c[0] {
+            b'\r' | b'\n' => {
+                println!();
+                return true;
+            }
+            b'\x7f' | b'\x08' => {
+                if line.pop().is_some() {
+                    print!("\x08 \x08");
+                }
+            }
+            c if (0x20..0x7f).contains(&c) && line.len() < MAX_LINE_LEN => {
+                line.push(c as char);
+                print!("{}", c as char);
+            }
+            _ => {}
+        }
+        stdout().flush();
+    }
+}
+
+fn help() {
+    println!("Built-in commands:");
+    println!("  help        Print this help");
+    println!("  exit [n]    Exit the shell with status n");
+    println!("  ps          List processes");
+    println!("  dmesg [-c]  Print the kernel log, then clear it with -c");
+    println!("  dmesg -n l  Echo kernel messages up to level l (1-5) to the console");
+    println!("  kill p [s]  Send signal s, TERM by default, to process p");
+    println!("  top [-d s] [-n n]");
+    println!("              Show the busiest processes every s seconds, n times");
+    println!("Anything else runs a program from the initramfs:");
+    println!("  prog args   Run prog with args");
+    println!("  a | b       Pipe the output of a into b");
+}
+
+// Run every stage of a pipeline in its own child and wait for all of them
+fn run_pipeline(stages: &[Vec<&str>]) {
+    let mut children = Vec::new();
+    let mut stdin = None;
+    for (i, argv) in stages.iter().enumerate() {
+        let pipe = if i + 1 < stages.len() {
+            match syscall::pipe() {
+                Ok(fds) => Some(fds),
+                Err(err) => {
+                    println!("sh: pipe: {}", err);
+                    break;
+                }
+            }
+        } else {
+            None
+        };
+        match syscall::fork() {
+            Ok(0) => {
+                if let Some(fd) = stdin {
+                    let _ = syscall::dup2(fd, STDIN);
+                    let _ = syscall::close(fd);
+                }
+                if let Some([rfd, wfd]) = pipe {
+                    let _ = syscall::close(rfd);
+                    let _ = syscall::dup2(wfd, STDOUT);
+                    let _ = syscall::close(wfd);
+                }
+                let _ = syscall::exec(argv[0], argv);
+                println!("sh: {}: command not found", argv[0]);
+                syscall::exit(EXEC_FAILED);
+            }
+            Ok(pid) => children.push(pid),
+            Err(err) => println!("sh: fork: {}", err),
+        }
+        if let Some(fd) = stdin.take() {
+            let _ = syscall::close(fd);
+        }
+        if let Some([rfd, wfd]) = pipe {
+            let _ = syscall::close(wfd);
+            stdin = Some(rfd);
+        }
+    }
+    if let Some(fd) = stdin {
+        let _ = syscall::close(fd);
+    }
+    for pid in children {
+        let _ = syscall::waitpid(pid as i64, 0);
+    }
+}
+
+// Returns the exit status if the shell should exit
+fn execute(line: &str) -> Option<i32> {
+    let stages: Vec<Vec<&str>> = line
+        .split('|')
+        .map(|stage| stage.split_whitespace().collect())
+        .collect();
+    if stages.len() == 1 && stages[0].is_empty() {
+        return None;
+    }
+    if stages.iter().any(|argv| argv.is_empty()) {
+        println!("sh: syntax error near '|'");
+        return None;
+    }
+    if stages.len() == 1 {
+        let argv = &stages[0];
+        match argv[0] {
+            "help" => {
+                help();
+                return None;
+            }
+            "exit" => {
+                return Some(argv.get(1).and_then(|n| n.parse().ok()).unwrap_or(0));
+            }
+            "dmesg" => {
+                dmesg::dmesg(argv);
+                return None;
+            }
+            "ps" => {
+                ps::ps();
+                return None;
+            }
+            "kill" => {
+                ps::kill(argv);
+                return None;
+            }
+            "top" => {
+                ps::top(argv);
+                return None;
+            }
+            _ => {}
+        }
+    }
+    run_pipeline(&stages);
+    None
+}
+
+#[no_mangle]
+fn main(_: Args) -> i32 {
+    let mut line = String::new();
+    loop {
+        print!("$ ");
+        stdout().flush();
+        if !read_line(&mut line) {
+            return 0;
+        }
+        if let Some(status) = execute(&line) {
+            return status;
+        }
+    }
+}
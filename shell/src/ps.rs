@@ -0,0 +1,159 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::time::Duration;
+use ulib::abi::signal;
+use ulib::abi::types::ProcInfo;
+use ulib::io::stdout;
+use ulib::{print, println, process, syscall, time};
+
+const TOP_DELAY: Duration = Duration::from_secs(1);
+const TOP_ITERATIONS: usize = 10;
+
+// Seconds with millisecond precision
+fn cpu_time(ns: u64) -> (u64, u64) {
+    (ns / 1_000_000_000, ns / 1_000_000 % 1000)
+}
+
+fn print_header(cpu_usage: bool) {
+    if cpu_usage {
+        println!("  PID  PPID S  %CPU       TIME    CSW      MEM COMMAND");
+    } else {
+        println!("  PID  PPID S       TIME    CSW      MEM COMMAND");
+    }
+}
+
+// cpu_usage is in permille of the elapsed time
+fn print_row(info: &ProcInfo, cpu_usage: Option<u64>) {
+    let (sec, ms) = cpu_time(info.cpu_ns);
+    let usage = match cpu_usage {
+        Some(usage) => format!(" {:3}.{}", usage / 10, usage % 10),
+        None => String::new(),
+    };
+    println!(
+        "{:5} {:5} {}{} {:6}.{:03} {:6} {:7}K {}",
+        info.pid,
+        info.ppid,
+        info.state as char,
+        usage,
+        sec,
+        ms,
+        info.switches,
+        info.mem / 1024,
+        info.name()
+    );
+}
+
+pub fn ps() {
+    let infos = match process::list() {
+        Ok(infos) => infos,
+        Err(err) => {
+            println!("ps: {}", err);
+            return;
+        }
+    };
+    print_header(false);
+    for info in infos.iter() {
+        print_row(info, None);
+    }
+}
+
+// kill <pid> [sig], the signal is a number or a name like TERM or SIGTERM
+pub fn kill(argv: &[&str]) {
+    let (pid, sig) = match argv {
+        [_, pid] => (*pid, "TERM"),
+        [_, pid, sig] => (*pid, sig.trim_start_matches('-')),
+        _ => {
+            println!("Usage: kill <pid> [sig]");
+            return;
+        }
+    };
+    let pid = match pid.parse::<u64>() {
+        Ok(pid) => pid,
+        Err(_) => {
+            println!("kill: invalid pid: {}", pid);
+            return;
+        }
+    };
+    let sig = match sig.parse::<usize>().ok().or_else(|| signal::from_name(sig)) {
+        Some(sig) => sig,
+        None => {
+            println!("kill: invalid signal: {}", sig);
+            return;
+        }
+    };
+    if let Err(err) = syscall::signal_kill(pid, sig) {
+        println!("kill: ({}): {}", pid, err);
+    }
+}
+
+fn parse_top_args(argv: &[&str]) -> Option<(Duration, usize)> {
+    let mut delay = TOP_DELAY;
+    let mut iterations = TOP_ITERATIONS;
+    let mut args = argv[1..].iter();
+    while let Some(arg) = args.next() {
+        let value = args.next()?.parse::<u64>().ok()?;
+        match *arg {
+            "-d" => delay = Duration::from_secs(value),
+            "-n" => iterations = value as usize,
+            _ => return None,
+        }
+    }
+    if delay.is_zero() {
+        return None;
+    }
+    Some((delay, iterations))
+}
+
+// Redraw the process list every delay seconds, busiest processes first
+pub fn top(argv: &[&str]) {
+    let (delay, iterations) = match parse_top_args(argv) {
+        Some(args) => args,
+        None => {
+            println!("Usage: top [-d seconds] [-n iterations]");
+            return;
+        }
+    };
+    let mut prev = process::list().unwrap_or_default();
+    let mut last = time::now();
+    for i in 0..iterations {
+        let _ = time::sleep(delay);
+        let infos = match process::list() {
+            Ok(infos) => infos,
+            Err(err) => {
+                println!("top: {}", err);
+                return;
+            }
+        };
+        let now = time::now();
+        let elapsed = (now - last).as_nanos().max(1) as u64;
+        let mut rows: Vec<(u64, &ProcInfo)> = infos
+            .iter()
+            .map(|info| {
+                // A pid may have been reused by a process with less CPU time
+                let before = prev
+                    .iter()
+                    .find(|p| p.pid == info.pid && p.cpu_ns <= info.cpu_ns)
+                    .map_or(0, |p| p.cpu_ns);
+                ((info.cpu_ns - before) * 1000 / elapsed, info)
+            })
+            .collect();
+        rows.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.pid.cmp(&b.1.pid)));
+        // Clear the screen and move the cursor home
+        print!("\x1b[2J\x1b[H");
+        println!(
+            "top - up {}s, {} processes, refresh {}/{}",
+            now.as_secs(),
+            infos.len(),
+            i + 1,
+            iterations
+        );
+        print_header(true);
+        for (usage, info) in rows {
+            print_row(info, Some(usage));
+        }
+        stdout().flush();
+        prev = infos;
+        last = now;
+    }
+}
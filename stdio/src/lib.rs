@@ -1,5 +1,6 @@
 #![no_std]
 
+pub mod log;
 pub mod macros;
 use driver::uart;
 
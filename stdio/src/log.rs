@@ -0,0 +1,253 @@
+// Leveled kernel log. A message that passes the filter of its module is kept
+// in a ring buffer for dmesg, and echoed to the console if it is at least as
+// severe as the console level.
+
+use super::macros::UartWriter;
+use core::arch::asm;
+use core::fmt::{self, Write};
+use core::ptr::addr_of_mut;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl Level {
+    pub const ALL: [Level; 5] = [
+        Level::Error,
+        Level::Warn,
+        Level::Info,
+        Level::Debug,
+        Level::Trace,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "error",
+            Level::Warn => "warn",
+            Level::Info => "info",
+            Level::Debug => "debug",
+            Level::Trace => "trace",
+        }
+    }
+
+    // Accepts a name like "debug" or a number from 1 to 5
+    pub fn parse(s: &str) -> Option<Level> {
+        if let Ok(n) = s.parse::<u8>() {
+            return Level::ALL.iter().copied().find(|level| *level as u8 == n);
+        }
+        Level::ALL
+            .iter()
+            .copied()
+            .find(|level| level.name().eq_ignore_ascii_case(s))
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+pub const LOG_BUF_SIZE: usize = 0x4000;
+const MAX_FILTERS: usize = 16;
+const MODULE_LEN: usize = 48;
+
+#[derive(Clone, Copy)]
+struct Filter {
+    module: [u8; MODULE_LEN],
+    len: usize,
+    level: Level,
+}
+
+impl Filter {
+    fn module(&self) -> &str {
+        core::str::from_utf8(&self.module[..self.len]).unwrap_or("")
+    }
+
+    // A filter for a::b also covers a::b::c
+    fn matches(&self, module: &str) -> bool {
+        let prefix = self.module();
+        module == prefix || module.starts_with(prefix) && module[prefix.len()..].starts_with("::")
+    }
+}
+
+struct Logger {
+    buf: [u8; LOG_BUF_SIZE],
+    // Next byte to write
+    head: usize,
+    len: usize,
+    console_level: Level,
+    default_level: Level,
+    filters: [Option<Filter>; MAX_FILTERS],
+}
+
+static mut LOGGER: Logger = Logger {
+    buf: [0; LOG_BUF_SIZE],
+    head: 0,
+    len: 0,
+    console_level: Level::Info,
+    default_level: Level::Debug,
+    filters: [None; MAX_FILTERS],
+};
+
+impl Logger {
+    fn push(&mut self, c: u8) {
+        self.buf[self.head] = c;
+        self.head = (self.head + 1) % LOG_BUF_SIZE;
+        self.len = (self.len + 1).min(LOG_BUF_SIZE);
+    }
+
+    fn max_level(&self, module: &str) -> Level {
+        self.filters
+            .iter()
+            .flatten()
+            .filter(|filter| filter.matches(module))
+            .max_by_key(|filter| filter.len)
+            .map_or(self.default_level, |filter| filter.level)
+    }
+}
+
+impl Write for Logger {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &c in s.as_bytes() {
+            self.push(c);
+        }
+        Ok(())
+    }
+}
+
+fn logger() -> &'static mut Logger {
+    unsafe { &mut *addr_of_mut!(LOGGER) }
+}
+
+// Seconds and microseconds since boot
+fn uptime() -> (u64, u64) {
+    let now: u64;
+    let freq: u64;
+    unsafe {
+        asm!(
+            "mrs {0}, cntpct_el0",
+            "mrs {1}, cntfrq_el0",
+            out(reg) now,
+            out(reg) freq,
+        );
+    }
+    if freq == 0 {
+        return (0, 0);
+    }
+    (now / freq, (now % freq) * 1_000_000 / freq)
+}
+
+pub fn enabled(level: Level, module: &str) -> bool {
+    level <= logger().max_level(module)
+}
+
+pub fn log(level: Level, module: &str, args: fmt::Arguments) {
+    let (sec, usec) = uptime();
+    let logger = logger();
+    let _ = writeln!(
+        logger,
+        "[{:5}.{:06}] {:<5} {}: {}",
+        sec, usec, level, module, args
+    );
+    if level <= logger.console_level {
+        let _ = write!(
+            UartWriter,
+            "[{:5}.{:06}] {:<5} {}: {}",
+            sec, usec, level, module, args
+        );
+        let _ = UartWriter.write_str("\r\n");
+    }
+}
+
+pub fn console_level() -> Level {
+    logger().console_level
+}
+
+pub fn set_console_level(level: Level) {
+    logger().console_level = level;
+}
+
+// The level of modules without a filter of their own
+pub fn default_level() -> Level {
+    logger().default_level
+}
+
+pub fn set_default_level(level: Level) {
+    logger().default_level = level;
+}
+
+// Passing None removes the filter. Returns false if the table is full or the
+// module name is too long.
+pub fn set_filter(module: &str, level: Option<Level>) -> bool {
+    let filters = &mut logger().filters;
+    let pos = filters
+        .iter()
+        .position(|filter| filter.is_some_and(|filter| filter.module() == module));
+    let level = match level {
+        Some(level) => level,
+        None => {
+            if let Some(pos) = pos {
+                filters[pos] = None;
+            }
+            return true;
+        }
+    };
+    if module.len() > MODULE_LEN {
+        return false;
+    }
+    let pos = match pos.or_else(|| filters.iter().position(|filter| filter.is_none())) {
+        Some(pos) => pos,
+        None => return false,
+    };
+    let mut filter = Filter {
+        module: [0; MODULE_LEN],
+        len: module.len(),
+        level,
+    };
+    filter.module[..module.len()].copy_from_slice(module.as_bytes());
+    filters[pos] = Some(filter);
+    true
+}
+
+pub fn for_each_filter(mut f: impl FnMut(&str, Level)) {
+    for filter in logger().filters.iter().flatten() {
+        f(filter.module(), filter.level);
+    }
+}
+
+// Copy the newest messages that fit into buf, starting at a line boundary
+pub fn read(buf: &mut [u8]) -> usize {
+    let logger = logger();
+    let mut len = logger.len.min(buf.len());
+    let mut start = (logger.head + LOG_BUF_SIZE - len) % LOG_BUF_SIZE;
+    // Once older bytes are cut off, either by buf or by the ring wrapping
+    // around, the first line may be partial and is dropped
+    let cut = len < logger.len || logger.len == LOG_BUF_SIZE;
+    let at_line =
+        len < logger.len && logger.buf[(start + LOG_BUF_SIZE - 1) % LOG_BUF_SIZE] == b'\n';
+    if cut && !at_line {
+        while len > 0 {
+            let c = logger.buf[start];
+            start = (start + 1) % LOG_BUF_SIZE;
+            len -= 1;
+            if c == b'\n' {
+                break;
+            }
+        }
+    }
+    for (i, c) in buf[..len].iter_mut().enumerate() {
+        *c = logger.buf[(start + i) % LOG_BUF_SIZE];
+    }
+    len
+}
+
+pub fn clear() {
+    logger().len = 0;
+}
@@ -24,11 +24,38 @@ macro_rules! println {
     });
 }
 
+// Log a message of the module it is called from, see stdio::log
 #[macro_export]
-macro_rules! debug {
-    ($($arg:tt)*) => ({
-        stdio::print!("[{}:{}] ", file!(), line!());
-        stdio::print!($($arg)*);
-        stdio::print!("\r\n");
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => ({
+        let level = $level;
+        if stdio::log::enabled(level, module_path!()) {
+            stdio::log::log(level, module_path!(), format_args!($($arg)*));
+        }
     });
 }
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => (stdio::log!(stdio::log::Level::Error, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => (stdio::log!(stdio::log::Level::Warn, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => (stdio::log!(stdio::log::Level::Info, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => (stdio::log!(stdio::log::Level::Debug, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => (stdio::log!(stdio::log::Level::Trace, $($arg)*));
+}
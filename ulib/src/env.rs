@@ -0,0 +1,53 @@
+static mut ARGC: usize = 0;
+static mut ARGV: *const *const u8 = core::ptr::null();
+
+pub(crate) unsafe fn init(argc: usize, argv: *const *const u8) {
+    ARGC = argc;
+    ARGV = argv;
+}
+
+// Iterator over the arguments the kernel copied to the top of the stack
+#[derive(Clone, Copy, Debug)]
+pub struct Args {
+    idx: usize,
+    argc: usize,
+    argv: *const *const u8,
+}
+
+pub fn args() -> Args {
+    unsafe {
+        Args {
+            idx: 0,
+            argc: ARGC,
+            argv: ARGV,
+        }
+    }
+}
+
+unsafe fn c_str(ptr: *const u8) -> &'static str {
+    let mut len = 0;
+    while *ptr.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8_unchecked(core::slice::from_raw_parts(ptr, len))
+}
+
+impl Iterator for Args {
+    type Item = &'static str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.idx >= self.argc {
+            return None;
+        }
+        let arg = unsafe { c_str(*self.argv.add(self.idx)) };
+        self.idx += 1;
+        Some(arg)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.argc - self.idx;
+        (left, Some(left))
+    }
+}
+
+impl ExactSizeIterator for Args {}
@@ -0,0 +1,73 @@
+use crate::syscall::sbrk;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::null_mut;
+
+// Blocks are carved from the heap in power of two size classes from 16 bytes
+// up to 1 GiB and recycled through per class free lists.
+const MIN_CLASS: usize = 4;
+const NCLASS: usize = 27;
+const PAGE_SIZE: usize = 0x1000;
+
+struct FreeBlock {
+    next: *mut FreeBlock,
+}
+
+pub struct UserAllocator {
+    free_lists: [*mut FreeBlock; NCLASS],
+}
+
+#[global_allocator]
+static mut ALLOCATOR: UserAllocator = UserAllocator {
+    free_lists: [null_mut(); NCLASS],
+};
+
+fn class(layout: &Layout) -> Option<usize> {
+    let size = layout.size().max(layout.align()).max(1 << MIN_CLASS);
+    let class = size.next_power_of_two().trailing_zeros() as usize - MIN_CLASS;
+    if class < NCLASS {
+        Some(class)
+    } else {
+        None
+    }
+}
+
+// Grow the heap by one block of the class, aligned to its size up to a page
+unsafe fn grow(class: usize) -> *mut u8 {
+    let size = 1 << (class + MIN_CLASS);
+    let align = size.min(PAGE_SIZE);
+    let cur = match sbrk(0) {
+        Ok(cur) => cur as usize,
+        Err(_) => return null_mut(),
+    };
+    let pad = (align - cur % align) % align;
+    match sbrk((pad + size) as i64) {
+        Ok(ret) => (ret as usize + pad) as *mut u8,
+        Err(_) => null_mut(),
+    }
+}
+
+unsafe impl GlobalAlloc for UserAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.align() > PAGE_SIZE {
+            return null_mut();
+        }
+        let class = match class(&layout) {
+            Some(class) => class,
+            None => return null_mut(),
+        };
+        let head = ALLOCATOR.free_lists[class];
+        if head.is_null() {
+            grow(class)
+        } else {
+            ALLOCATOR.free_lists[class] = (*head).next;
+            head as *mut u8
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let class = class(&layout).unwrap();
+        let block = ptr as *mut FreeBlock;
+        (*block).next = ALLOCATOR.free_lists[class];
+        ALLOCATOR.free_lists[class] = block;
+    }
+}
@@ -0,0 +1,76 @@
+use crate::syscall::write;
+use core::fmt;
+
+pub const STDIN: i32 = 0;
+pub const STDOUT: i32 = 1;
+
+const BUF_SIZE: usize = 256;
+
+// Output is collected here and written out on newline, when full or at exit
+pub struct Stdout {
+    buf: [u8; BUF_SIZE],
+    len: usize,
+}
+
+static mut STDOUT_BUF: Stdout = Stdout {
+    buf: [0; BUF_SIZE],
+    len: 0,
+};
+
+pub fn stdout() -> &'static mut Stdout {
+    unsafe { &mut *core::ptr::addr_of_mut!(STDOUT_BUF) }
+}
+
+impl Stdout {
+    pub fn flush(&mut self) {
+        let mut written = 0;
+        while written < self.len {
+            match write(STDOUT, &self.buf[written..self.len]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => written += n,
+            }
+        }
+        self.len = 0;
+    }
+
+    fn push(&mut self, c: u8) {
+        if self.len == BUF_SIZE {
+            self.flush();
+        }
+        self.buf[self.len] = c;
+        self.len += 1;
+        if c == b'\n' {
+            self.flush();
+        }
+    }
+}
+
+impl fmt::Write for Stdout {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &c in s.as_bytes() {
+            self.push(c);
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    let _ = fmt::write(stdout(), args);
+}
+
+#[macro_export]
+macro_rules! print {
+    ($($arg:tt)*) => ({
+        $crate::io::_print(format_args!($($arg)*));
+    });
+}
+
+#[macro_export]
+macro_rules! println {
+    () => ($crate::print!("\r\n"));
+    ($($arg:tt)*) => ({
+        $crate::print!($($arg)*);
+        $crate::print!("\r\n");
+    });
+}
@@ -0,0 +1,15 @@
+#![no_std]
+
+extern crate alloc;
+
+pub mod env;
+mod heap;
+pub mod io;
+pub mod process;
+mod rt;
+pub mod syscall;
+pub mod time;
+
+pub use abi;
+pub use abi::errno;
+pub use abi::Errno;
@@ -0,0 +1,30 @@
+use crate::syscall::{exec, exit, fork, procinfo, Result};
+use abi::types::ProcInfo;
+use alloc::vec;
+use alloc::vec::Vec;
+
+// Status a child exits with when the program cannot be executed
+pub const EXEC_FAILED: i32 = 127;
+
+// Run path in a new child process, returning its pid
+pub fn spawn(path: &str, args: &[&str]) -> Result<u64> {
+    let pid = fork()?;
+    if pid == 0 {
+        let _ = exec(path, args);
+        exit(EXEC_FAILED);
+    }
+    Ok(pid)
+}
+
+// Every process in the system, retrying if more were created meanwhile
+pub fn list() -> Result<Vec<ProcInfo>> {
+    let mut infos = vec![ProcInfo::default(); 16];
+    loop {
+        let count = procinfo(&mut infos)?;
+        if count <= infos.len() {
+            infos.truncate(count);
+            return Ok(infos);
+        }
+        infos.resize(count * 2, ProcInfo::default());
+    }
+}
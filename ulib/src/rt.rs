@@ -0,0 +1,26 @@
+#![cfg(not(test))]
+
+use crate::env::{self, Args};
+use crate::println;
+use crate::syscall::exit;
+use core::panic::PanicInfo;
+
+extern "Rust" {
+    // Provided by the program as `#[no_mangle] fn main(args: Args) -> i32`
+    fn main(args: Args) -> i32;
+}
+
+// The kernel starts a program at address 0 with argc in x0 and argv in x1
+#[no_mangle]
+#[link_section = ".text.main"]
+unsafe extern "C" fn _start(argc: usize, argv: *const *const u8) -> ! {
+    env::init(argc, argv);
+    let status = main(env::args());
+    exit(status)
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    println!("{}", info);
+    exit(101)
+}
@@ -0,0 +1,289 @@
+use abi::types::{ProcInfo, Timespec};
+use abi::{Errno, Sysno};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::arch::asm;
+
+pub use abi::mman::*;
+pub use abi::signal::{SIG_BLOCK, SIG_DFL, SIG_IGN, SIG_SETMASK, SIG_UNBLOCK};
+pub use abi::sysno::WNOHANG;
+pub use abi::sysno::{SYSLOG_CLEAR, SYSLOG_CONSOLE_LEVEL, SYSLOG_READ_ALL, SYSLOG_SIZE_BUFFER};
+pub use abi::types::CLOCK_MONOTONIC;
+
+pub type Result<T> = core::result::Result<T, Errno>;
+
+pub unsafe fn syscall0(sysno: Sysno) -> u64 {
+    let ret: u64;
+    asm!("svc 0", out("x0") ret, in("x8") sysno as u64);
+    ret
+}
+
+pub unsafe fn syscall1(sysno: Sysno, arg0: u64) -> u64 {
+    let ret: u64;
+    asm!("svc 0", inout("x0") arg0 => ret, in("x8") sysno as u64);
+    ret
+}
+
+pub unsafe fn syscall2(sysno: Sysno, arg0: u64, arg1: u64) -> u64 {
+    let ret: u64;
+    asm!("svc 0", inout("x0") arg0 => ret, in("x1") arg1, in("x8") sysno as u64);
+    ret
+}
+
+pub unsafe fn syscall3(sysno: Sysno, arg0: u64, arg1: u64, arg2: u64) -> u64 {
+    let ret: u64;
+    asm!(
+        "svc 0",
+        inout("x0") arg0 => ret,
+        in("x1") arg1,
+        in("x2") arg2,
+        in("x8") sysno as u64,
+    );
+    ret
+}
+
+pub unsafe fn syscall6(
+    sysno: Sysno,
+    arg0: u64,
+    arg1: u64,
+    arg2: u64,
+    arg3: u64,
+    arg4: u64,
+    arg5: u64,
+) -> u64 {
+    let ret: u64;
+    asm!(
+        "svc 0",
+        inout("x0") arg0 => ret,
+        in("x1") arg1,
+        in("x2") arg2,
+        in("x3") arg3,
+        in("x4") arg4,
+        in("x5") arg5,
+        in("x8") sysno as u64,
+    );
+    ret
+}
+
+// The kernel expects null terminated strings
+fn c_string(s: &str) -> String {
+    let mut ret = String::from(s);
+    ret.push('\0');
+    ret
+}
+
+pub fn get_pid() -> Result<u64> {
+    Errno::check(unsafe { syscall0(Sysno::GetPid) })
+}
+
+pub fn read(fd: i32, buf: &mut [u8]) -> Result<usize> {
+    let ret = unsafe {
+        syscall3(
+            Sysno::Read,
+            fd as u64,
+            buf.as_mut_ptr() as u64,
+            buf.len() as u64,
+        )
+    };
+    Errno::check(ret).map(|n| n as usize)
+}
+
+pub fn write(fd: i32, buf: &[u8]) -> Result<usize> {
+    let ret = unsafe {
+        syscall3(
+            Sysno::Write,
+            fd as u64,
+            buf.as_ptr() as u64,
+            buf.len() as u64,
+        )
+    };
+    Errno::check(ret).map(|n| n as usize)
+}
+
+// Replace the current program, args[0] is conventionally the program name
+pub fn exec(path: &str, args: &[&str]) -> Result<()> {
+    exec_with_stack(path, args, 0)
+}
+
+// Like exec, with a stack of stack_size bytes for the new program, 0 for the
+// default size
+pub fn exec_with_stack(path: &str, args: &[&str], stack_size: usize) -> Result<()> {
+    let path = c_string(path);
+    let args: Vec<String> = args.iter().map(|arg| c_string(arg)).collect();
+    let mut argv: Vec<u64> = args.iter().map(|arg| arg.as_ptr() as u64).collect();
+    argv.push(0);
+    let ret = unsafe {
+        syscall3(
+            Sysno::Exec,
+            path.as_ptr() as u64,
+            argv.as_ptr() as u64,
+            stack_size as u64,
+        )
+    };
+    Errno::check(ret).map(|_| ())
+}
+
+// Returns 0 in the child and the pid of the child in the parent
+pub fn fork() -> Result<u64> {
+    // Buffered output would otherwise be printed by both processes
+    crate::io::stdout().flush();
+    Errno::check(unsafe { syscall0(Sysno::Fork) })
+}
+
+// Returns the pid and exit status of an exited child, a negative pid waits
+// for any child. With WNOHANG the pid is 0 if no child has exited yet.
+pub fn waitpid(pid: i64, options: u64) -> Result<(u64, u64)> {
+    let mut status = 0u64;
+    let ret = unsafe {
+        syscall3(
+            Sysno::Wait,
+            pid as u64,
+            &mut status as *mut u64 as u64,
+            options,
+        )
+    };
+    Errno::check(ret).map(|pid| (pid, status))
+}
+
+pub fn wait() -> Result<(u64, u64)> {
+    waitpid(-1, 0)
+}
+
+pub fn exit(status: i32) -> ! {
+    crate::io::stdout().flush();
+    unsafe {
+        syscall1(Sysno::Exit, status as u64);
+    }
+    unreachable!("exit returned");
+}
+
+// Returns whether the firmware answered the request
+pub fn mbox_call(channel: u8, mbox: &mut [u32]) -> Result<bool> {
+    let ret = unsafe { syscall2(Sysno::MboxCall, channel as u64, mbox.as_mut_ptr() as u64) };
+    Errno::check(ret).map(|ret| ret == 1)
+}
+
+pub fn kill(pid: u64) -> Result<()> {
+    Errno::check(unsafe { syscall1(Sysno::Kill, pid) }).map(|_| ())
+}
+
+// Returns the previous handler
+pub fn sigaction(sig: usize, handler: fn(u64)) -> Result<u64> {
+    Errno::check(unsafe { syscall2(Sysno::Sigaction, sig as u64, handler as u64) })
+}
+
+pub fn signal_kill(pid: u64, sig: usize) -> Result<()> {
+    Errno::check(unsafe { syscall2(Sysno::SignalKill, pid, sig as u64) }).map(|_| ())
+}
+
+// Returns the previous mask
+pub fn sigprocmask(how: u64, set: u64) -> Result<u64> {
+    Errno::check(unsafe { syscall2(Sysno::Sigprocmask, how, set) })
+}
+
+// Returns the seconds left on the previous alarm
+pub fn alarm(seconds: u64) -> Result<u64> {
+    Errno::check(unsafe { syscall1(Sysno::Alarm, seconds) })
+}
+
+// Returns the milliseconds left on the previous timer
+pub fn setitimer(value_ms: u64, interval_ms: u64) -> Result<u64> {
+    Errno::check(unsafe { syscall2(Sysno::Setitimer, value_ms, interval_ms) })
+}
+
+// Returns the read and the write end
+pub fn pipe() -> Result<[i32; 2]> {
+    let mut fds = [0i32; 2];
+    Errno::check(unsafe { syscall1(Sysno::Pipe, fds.as_mut_ptr() as u64) })?;
+    Ok(fds)
+}
+
+pub fn close(fd: i32) -> Result<()> {
+    Errno::check(unsafe { syscall1(Sysno::Close, fd as u64) }).map(|_| ())
+}
+
+pub fn dup2(oldfd: i32, newfd: i32) -> Result<i32> {
+    Errno::check(unsafe { syscall2(Sysno::Dup2, oldfd as u64, newfd as u64) }).map(|fd| fd as i32)
+}
+
+pub fn open(path: &str) -> Result<i32> {
+    let path = c_string(path);
+    Errno::check(unsafe { syscall1(Sysno::Open, path.as_ptr() as u64) }).map(|fd| fd as i32)
+}
+
+// fd is ignored for MAP_ANONYMOUS mappings
+pub fn mmap(
+    addr: u64,
+    len: usize,
+    prot: u64,
+    flags: u64,
+    fd: i32,
+    offset: usize,
+) -> Result<*mut u8> {
+    let ret = unsafe {
+        syscall6(
+            Sysno::Mmap,
+            addr,
+            len as u64,
+            prot,
+            flags,
+            fd as u64,
+            offset as u64,
+        )
+    };
+    Errno::check(ret).map(|addr| addr as *mut u8)
+}
+
+pub fn munmap(addr: *mut u8, len: usize) -> Result<()> {
+    Errno::check(unsafe { syscall2(Sysno::Munmap, addr as u64, len as u64) }).map(|_| ())
+}
+
+pub fn mprotect(addr: *mut u8, len: usize, prot: u64) -> Result<()> {
+    Errno::check(unsafe { syscall3(Sysno::Mprotect, addr as u64, len as u64, prot) }).map(|_| ())
+}
+
+// Returns the new program break, which is unchanged if it could not be moved
+pub fn brk(addr: u64) -> Result<u64> {
+    Errno::check(unsafe { syscall1(Sysno::Brk, addr) })
+}
+
+// Returns the previous program break
+pub fn sbrk(increment: i64) -> Result<u64> {
+    Errno::check(unsafe { syscall1(Sysno::Sbrk, increment as u64) })
+}
+
+// Fills buf with up to buf.len() entries and returns the number of processes
+pub fn procinfo(buf: &mut [ProcInfo]) -> Result<usize> {
+    let ret = unsafe { syscall2(Sysno::Procinfo, buf.as_mut_ptr() as u64, buf.len() as u64) };
+    Errno::check(ret).map(|n| n as usize)
+}
+
+// Returns early if a signal arrives
+pub fn nanosleep(req: &Timespec) -> Result<()> {
+    Errno::check(unsafe { syscall1(Sysno::Nanosleep, req as *const Timespec as u64) }).map(|_| ())
+}
+
+pub fn clock_gettime(clock: u64) -> Result<Timespec> {
+    let mut tp = Timespec::default();
+    let ret = unsafe { syscall2(Sysno::ClockGettime, clock, &mut tp as *mut Timespec as u64) };
+    Errno::check(ret).map(|_| tp)
+}
+
+// Only SYSLOG_READ_ALL uses buf, it returns the number of bytes read
+pub fn syslog(action: u64, buf: &mut [u8]) -> Result<usize> {
+    let ret = unsafe {
+        syscall3(
+            Sysno::Syslog,
+            action,
+            buf.as_mut_ptr() as u64,
+            buf.len() as u64,
+        )
+    };
+    Errno::check(ret).map(|n| n as usize)
+}
+
+// Messages at least as severe as level, from 1 (errors) to 5, go to the console
+pub fn set_console_loglevel(level: u64) -> Result<()> {
+    let ret = unsafe { syscall3(Sysno::Syslog, SYSLOG_CONSOLE_LEVEL, 0, level) };
+    Errno::check(ret).map(|_| ())
+}
@@ -0,0 +1,17 @@
+use crate::syscall::{self, Result, CLOCK_MONOTONIC};
+use abi::types::Timespec;
+use core::time::Duration;
+
+// Time since boot
+pub fn now() -> Duration {
+    let tp = syscall::clock_gettime(CLOCK_MONOTONIC).unwrap_or_default();
+    Duration::new(tp.sec as u64, tp.nsec as u32)
+}
+
+pub fn sleep(duration: Duration) -> Result<()> {
+    let req = Timespec {
+        sec: duration.as_secs() as i64,
+        nsec: duration.subsec_nanos() as i64,
+    };
+    syscall::nanosleep(&req)
+}